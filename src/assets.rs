@@ -0,0 +1,295 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::{compress, copy_if_changed, Config, Error, ASSET_SRC_DIR};
+use blades::HashMap;
+
+use fnv::FnvHasher;
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::hash::Hasher;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// One asset to place into the output directory.
+pub(crate) struct Asset {
+    /// Path of the output, relative to the assets directory, with `/` as the separator.
+    name: String,
+    /// The name of the output file, fingerprinted if enabled.
+    output: String,
+    /// Where the asset is located.
+    src: PathBuf,
+    /// The contents to write instead of copying the source, if it was compiled or minified.
+    processed: Option<Vec<u8>>,
+}
+
+impl Asset {
+    /// The contents of the output.
+    fn contents(&self) -> Result<Vec<u8>, io::Error> {
+        match &self.processed {
+            Some(processed) => Ok(processed.clone()),
+            None => fs::read(&self.src),
+        }
+    }
+}
+
+/// Place assets located in the `assets` directory or in the `assets` subdirectory of the theme,
+/// if used, into a dedicated subdirectory of the output directory specified in the config
+/// (defaults to `assets`, too). Sass files are compiled to CSS and if enabled, CSS and JavaScript
+/// files are minified. Only the files that changed are written, together with their compressed
+/// variants, if enabled, and the files that are no longer among the assets are deleted.
+pub(crate) fn colocate(config: &Config, assets: &[Asset]) -> Result<(), Error> {
+    let output = Path::new(config.output_dir.as_ref()).join(config.site.assets.as_ref());
+    let mut colocated = BTreeSet::new();
+    for asset in assets {
+        let dest = output.join(&asset.output);
+        match &asset.processed {
+            Some(processed) => write_if_changed(&dest, processed)?,
+            None => copy_if_changed(&asset.src, &dest)?,
+        }
+        colocated.insert(dest);
+    }
+    if let Some(compression) = &config.compression {
+        let paths: Vec<_> = colocated.iter().cloned().collect();
+        colocated.extend(compression.compress(&paths, &compress::TEXT_ASSETS)?);
+    }
+    remove_others(&output, &colocated)?;
+    Ok(())
+}
+
+/// Names of the assets that can be used in templates, mapped to the URLs of their
+/// fingerprinted versions. The name is the path of the asset output in the assets directory
+/// with all the characters other than letters and digits replaced by `_`,
+/// so `css/style.css` (or `css/style.scss`) becomes `css_style_css`.
+/// Assets whose names would be the same are reported as an error.
+pub(crate) fn manifest(
+    config: &Config,
+    assets: &[Asset],
+) -> Result<HashMap<String, String>, Error> {
+    let url = config.site.url.trim_end_matches('/');
+    let mut names = BTreeMap::new();
+    let mut manifest = HashMap::default();
+    for asset in assets {
+        let key = asset.name.replace(|c: char| !c.is_alphanumeric(), "_");
+        if let Some(other) = names.insert(key.clone(), &asset.name) {
+            return Err(Error::AssetName(
+                other.as_str().into(),
+                asset.name.as_str().into(),
+            ));
+        }
+        let link = format!("{}/{}/{}", url, config.site.assets, asset.output);
+        manifest.insert(key, link);
+    }
+    Ok(manifest)
+}
+
+/// The `assets` subdirectory of the theme, if one is used.
+fn theme_assets(config: &Config) -> Option<PathBuf> {
+    if config.theme.is_empty() {
+        return None;
+    }
+    let mut dir = Path::new(config.theme_dir.as_ref()).join(config.theme.as_ref());
+    dir.push(ASSET_SRC_DIR);
+    Some(dir)
+}
+
+/// All the assets of the site and the theme, with the Sass files compiled and
+/// CSS and JavaScript minified and the output names fingerprinted, if enabled.
+/// The site assets take precedence over the theme ones.
+pub(crate) fn assets(config: &Config) -> Result<Vec<Asset>, Error> {
+    let theme = theme_assets(config);
+    let mut sources = BTreeMap::new();
+    if let Some(dir) = &theme {
+        collect(dir, "", &mut sources)?;
+    }
+    collect(Path::new(ASSET_SRC_DIR), "", &mut sources)?;
+
+    let overlay = Overlay(theme.as_deref());
+    let mut assets = Vec::with_capacity(sources.len());
+    for (name, src) in sources {
+        let sass = name
+            .strip_suffix(".scss")
+            .or_else(|| name.strip_suffix(".sass"));
+        let mut asset = match sass {
+            // Partials are only imported by other files
+            Some(_) if name.rsplit('/').next().unwrap_or_default().starts_with('_') => continue,
+            Some(stem) => Asset {
+                name: format!("{}.css", stem),
+                output: String::new(),
+                processed: Some(overlay.compile(&src)?.into_bytes()),
+                src,
+            },
+            None => Asset {
+                name,
+                output: String::new(),
+                src,
+                processed: None,
+            },
+        };
+        if config.site.minify {
+            asset.processed = minified(&asset)?.or(asset.processed);
+        }
+        asset.output = if config.fingerprint_assets {
+            fingerprinted(&asset.name, &asset.contents()?)
+        } else {
+            asset.name.clone()
+        };
+        assets.push(asset);
+    }
+    Ok(assets)
+}
+
+/// The minified contents of the asset, if it's a CSS or JavaScript file,
+/// unless it's minified already.
+fn minified(asset: &Asset) -> Result<Option<Vec<u8>>, Error> {
+    let name = asset.name.as_str();
+    let css = name.ends_with(".css") && !name.ends_with(".min.css");
+    let js = name.ends_with(".js") && !name.ends_with(".min.js");
+    if !css && !js {
+        return Ok(None);
+    }
+    let contents = asset.contents()?;
+    let Ok(source) = std::str::from_utf8(&contents) else {
+        return Ok(None);
+    };
+    let minified = if css {
+        minifier::css::minify(source)
+            .map_err(|e| Error::Minify(asset.src.to_string_lossy().into(), e.into()))?
+            .to_string()
+    } else {
+        minifier::js::minify(source).to_string()
+    };
+    Ok(Some(minified.into_bytes()))
+}
+
+/// The file system seen by the Sass compiler, in which the site assets override
+/// the ones of the theme in the contained directory, so the site can replace its partials.
+#[derive(Debug)]
+struct Overlay<'a>(Option<&'a Path>);
+
+impl Overlay<'_> {
+    fn resolve<'p>(&self, path: &'p Path) -> Cow<'p, Path> {
+        if let Some(relative) = self.0.and_then(|theme| path.strip_prefix(theme).ok()) {
+            let site = Path::new(ASSET_SRC_DIR).join(relative);
+            if site.exists() {
+                return site.into();
+            }
+        }
+        path.into()
+    }
+
+    /// Compile the Sass file, looking up imports also from the site and theme assets directories.
+    fn compile(&self, path: &Path) -> Result<String, Error> {
+        let mut options = grass::Options::default()
+            .fs(self)
+            .load_path(Path::new(ASSET_SRC_DIR));
+        if let Some(theme) = self.0 {
+            options = options.load_path(theme);
+        }
+        grass::from_path(path, &options).map_err(|e| {
+            let description = e.to_string();
+            match (*e).kind() {
+                grass::ErrorKind::ParseError { message, loc, .. } => {
+                    Error::Sass(loc.file.name().into(), loc.begin.line + 1, message.into())
+                }
+                _ => Error::Sass(path.to_string_lossy().into(), 0, description.into()),
+            }
+        })
+    }
+}
+
+impl grass::Fs for Overlay<'_> {
+    fn is_dir(&self, path: &Path) -> bool {
+        self.resolve(path).is_dir()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.resolve(path).is_file()
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, io::Error> {
+        fs::read(self.resolve(path))
+    }
+}
+
+fn collect(
+    dir: &Path,
+    prefix: &str,
+    sources: &mut BTreeMap<String, PathBuf>,
+) -> Result<(), io::Error> {
+    let iter = match fs::read_dir(dir) {
+        Ok(iter) => iter,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in iter.filter_map(Result::ok) {
+        let file_type = entry.file_type()?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if file_type.is_file() {
+            sources.insert(name, entry.path());
+        } else if file_type.is_dir() {
+            collect(&entry.path(), &(name + "/"), sources)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the data to the file, unless it already contains them.
+fn write_if_changed(path: &Path, data: &[u8]) -> Result<(), io::Error> {
+    if fs::read(path).is_ok_and(|old| old == data) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data)
+}
+
+/// Insert the hash of the data before the extension of the file name,
+/// such as `css/style.3fa9c1d2.css`.
+fn fingerprinted(name: &str, data: &[u8]) -> String {
+    let mut hasher = FnvHasher::default();
+    hasher.write(data);
+    let hash = (hasher.finish() >> 32) as u32;
+    let file_start = name.rfind('/').map_or(0, |i| i + 1);
+    match name[file_start..].rfind('.').filter(|&i| i > 0) {
+        Some(i) => {
+            let (stem, extension) = name.split_at(file_start + i);
+            format!("{}.{:08x}{}", stem, hash, extension)
+        }
+        None => format!("{}.{:08x}", name, hash),
+    }
+}
+
+/// Delete all the files in the directory that are not among the `kept` ones,
+/// as well as the directories that become empty. Return whether the directory is empty.
+fn remove_others(dir: &Path, kept: &BTreeSet<PathBuf>) -> Result<bool, io::Error> {
+    let iter = match fs::read_dir(dir) {
+        Ok(iter) => iter,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(true),
+        Err(e) => return Err(e),
+    };
+    let mut empty = true;
+    for entry in iter.filter_map(Result::ok) {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if remove_others(&path, kept)? {
+                fs::remove_dir(&path)?;
+            } else {
+                empty = false;
+            }
+        } else if kept.contains(&path) {
+            empty = false;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(empty)
+}
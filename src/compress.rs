@@ -0,0 +1,136 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::{default_true, Unwind};
+
+use brotli::CompressorWriter;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// Extensions of the rendered files that are compressed.
+pub(crate) const RENDERED: [&str; 3] = ["html", "xml", "json"];
+/// Extensions of the text assets that are compressed.
+pub(crate) const TEXT_ASSETS: [&str; 10] = [
+    "css",
+    "js",
+    "mjs",
+    "json",
+    "svg",
+    "txt",
+    "xml",
+    "html",
+    "map",
+    "webmanifest",
+];
+
+/// Settings of writing the compressed variants of the files, for the servers that can serve them.
+#[derive(Deserialize)]
+pub(crate) struct CompressConfig {
+    /// Write the gzip variants, with the `.gz` extension added?
+    #[serde(default = "default_true")]
+    gzip: bool,
+    /// Write the Brotli variants, with the `.br` extension added?
+    #[serde(default = "default_true")]
+    brotli: bool,
+    /// Files smaller than this number of bytes are not compressed.
+    #[serde(default = "default_min_size")]
+    min_size: u64,
+}
+
+#[inline]
+const fn default_min_size() -> u64 {
+    1024
+}
+
+impl CompressConfig {
+    /// Write the compressed variants of the files with one of the `extensions` in parallel,
+    /// unless they are newer than the files already. Return the paths of all the variants.
+    pub(crate) fn compress(
+        &self,
+        paths: &[PathBuf],
+        extensions: &[&str],
+    ) -> Result<Vec<PathBuf>, io::Error> {
+        let paths: Vec<&Path> = paths
+            .iter()
+            .map(PathBuf::as_path)
+            .filter(|path| {
+                let extension = path.extension().and_then(|ext| ext.to_str());
+                extension.is_some_and(|ext| extensions.contains(&ext))
+            })
+            .collect();
+        let num_threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        let per_thread = paths.len() / num_threads + 1;
+
+        thread::scope(|s| {
+            let threads: Vec<_> = paths
+                .chunks(per_thread)
+                .map(|chunk| {
+                    s.spawn(move || {
+                        let mut variants = Vec::with_capacity(chunk.len());
+                        for path in chunk {
+                            self.compress_file(path, &mut variants)?;
+                        }
+                        Ok::<_, io::Error>(variants)
+                    })
+                })
+                .collect();
+            let mut variants = Vec::with_capacity(paths.len());
+            for thread in threads {
+                variants.append(&mut thread.join().unwind()?);
+            }
+            Ok(variants)
+        })
+    }
+
+    fn compress_file(&self, path: &Path, variants: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+        let meta = fs::metadata(path)?;
+        if meta.len() < self.min_size {
+            return Ok(());
+        }
+        let mut data = None;
+        for (enabled, extension) in [(self.gzip, ".gz"), (self.brotli, ".br")] {
+            if !enabled {
+                continue;
+            }
+            let mut variant = OsString::from(path);
+            variant.push(extension);
+            let variant = PathBuf::from(variant);
+            let outdated = match fs::metadata(&variant) {
+                Ok(compressed) => compressed.modified()? < meta.modified()?,
+                Err(_) => true,
+            };
+            if outdated {
+                let data = match &data {
+                    Some(data) => data,
+                    None => data.insert(fs::read(path)?),
+                };
+                let writer = BufWriter::new(File::create(&variant)?);
+                if extension == ".gz" {
+                    let mut encoder = GzEncoder::new(writer, Compression::best());
+                    encoder.write_all(data)?;
+                    encoder.finish()?.flush()?;
+                } else {
+                    let mut encoder = CompressorWriter::new(writer, 4096, 11, 22);
+                    encoder.write_all(data)?;
+                    encoder.into_inner().flush()?;
+                }
+            }
+            variants.push(variant);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::{Config, Error, ParseError};
+use blades::{Any, HashMap};
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+/// Where the global data are located, relative to the site directory.
+pub(crate) static DATA_DIR: &str = "data";
+
+/// One file from the data directory.
+pub(crate) struct DataFile {
+    /// Path of the file relative to the data directory, without the extension.
+    name: String,
+    extension: String,
+    path: PathBuf,
+    source: Vec<u8>,
+}
+
+/// Read all the data files from the data directory and from the theme, if one is used.
+/// The files from the site come first, so that they override the ones from the theme.
+pub(crate) fn read_data(config: &Config) -> Result<Vec<DataFile>, io::Error> {
+    let mut files = Vec::new();
+    read_dir(Path::new(DATA_DIR), "", &mut files)?;
+    if !config.theme.is_empty() {
+        let mut theme_path = Path::new(config.theme_dir.as_ref()).join(config.theme.as_ref());
+        theme_path.push(DATA_DIR);
+        read_dir(&theme_path, "", &mut files)?;
+    }
+    Ok(files)
+}
+
+fn read_dir(dir: &Path, prefix: &str, files: &mut Vec<DataFile>) -> Result<(), io::Error> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let (stem, extension) = match (path.file_stem(), path.extension()) {
+            (Some(stem), extension) => (
+                stem.to_string_lossy(),
+                extension.unwrap_or_default().to_string_lossy(),
+            ),
+            _ => continue,
+        };
+        if path.is_dir() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            read_dir(&path, &format!("{}{}/", prefix, name), files)?;
+        } else if matches!(extension.as_ref(), "toml" | "yaml" | "yml" | "json" | "csv") {
+            files.push(DataFile {
+                name: format!("{}{}", prefix, stem),
+                extension: extension.into_owned(),
+                source: fs::read(&path)?,
+                path,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Parse the data files into one tree, where every file is found under its name
+/// and every subdirectory is a nested map.
+pub(crate) fn parse_data(files: &[DataFile]) -> Result<Any<'_>, Error> {
+    let mut data = HashMap::default();
+    for file in files {
+        let value =
+            parse_file(file).map_err(|e| Error::Parse(e, file.path.to_string_lossy().into()))?;
+
+        let mut segments = file.name.split('/');
+        let last = segments.next_back().unwrap_or_default();
+        let mut map = Some(&mut data);
+        for segment in segments {
+            let entry = map.map(|map| {
+                map.entry(segment)
+                    .or_insert_with(|| Any::Map(HashMap::default()))
+            });
+            // A file that came earlier can be in place of the directory, then it takes precedence
+            map = match entry {
+                Some(Any::Map(m)) => Some(m),
+                _ => None,
+            };
+        }
+        // Files from the site come first, so they are not overwritten by the theme.
+        if let Some(map) = map {
+            map.entry(last).or_insert(value);
+        }
+    }
+    Ok(Any::Map(data))
+}
+
+fn parse_file(file: &DataFile) -> Result<Any<'_>, ParseError> {
+    Ok(match file.extension.as_str() {
+        "toml" => toml::from_slice(&file.source)?,
+        "yaml" | "yml" => serde_yaml::from_slice(&file.source)?,
+        "json" => serde_json::from_slice(&file.source)?,
+        _ => parse_csv(std::str::from_utf8(&file.source)?)?,
+    })
+}
+
+/// Parse CSV with a header into a list of maps from the column names to the values.
+fn parse_csv(source: &str) -> Result<Any<'_>, ParseError> {
+    let mut records = csv_records(source)?.into_iter();
+    let header = records.next().unwrap_or_default();
+    let list = records
+        .map(|record| {
+            let mut map = HashMap::default();
+            let fields = header.iter().zip(record).map(|(&key, value)| {
+                let value = if value.contains("\"\"") {
+                    value.replace("\"\"", "\"").into()
+                } else {
+                    value.into()
+                };
+                (key, Any::String(value))
+            });
+            map.extend(fields);
+            Any::Map(map)
+        })
+        .collect();
+    Ok(Any::List(list))
+}
+
+/// Split the CSV source into records of fields.
+/// Quoted fields are returned without the surrounding quotes, but with the inner ones doubled.
+fn csv_records(source: &str) -> Result<Vec<Vec<&str>>, ParseError> {
+    let bytes = source.as_bytes();
+    let (mut records, mut record) = (Vec::new(), Vec::new());
+    let mut i = 0;
+    loop {
+        if bytes.get(i) == Some(&b'"') {
+            let start = i + 1;
+            i = start;
+            loop {
+                match bytes.get(i) {
+                    None => return Err(ParseError::Csv("unterminated quoted field")),
+                    Some(b'"') if bytes.get(i + 1) == Some(&b'"') => i += 2,
+                    Some(b'"') => break,
+                    Some(_) => i += 1,
+                }
+            }
+            record.push(&source[start..i]);
+            i += 1;
+        } else {
+            let start = i;
+            while i < bytes.len() && !matches!(bytes[i], b',' | b'\r' | b'\n') {
+                i += 1;
+            }
+            record.push(&source[start..i]);
+        }
+
+        match bytes.get(i) {
+            Some(b',') => i += 1,
+            Some(b'\r' | b'\n') | None => {
+                if bytes.get(i) == Some(&b'\r') {
+                    i += 1;
+                }
+                if bytes.get(i) == Some(&b'\n') {
+                    i += 1;
+                }
+                // Skip empty lines
+                if record.len() == 1 && record[0].is_empty() {
+                    record.clear();
+                } else {
+                    records.push(mem::take(&mut record));
+                }
+                if i >= bytes.len() {
+                    return Ok(records);
+                }
+            }
+            Some(_) => return Err(ParseError::Csv("unexpected character after a quoted field")),
+        }
+    }
+}
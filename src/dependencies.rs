@@ -0,0 +1,277 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::page::Page;
+use crate::taxonomies::Classification;
+use crate::types::HashMap;
+
+use fnv::FnvHasher;
+use std::collections::HashSet;
+use std::hash::Hasher;
+use std::path::PathBuf;
+
+/// What was rendered from which page and taxonomy in the last build, and how the pages
+/// were linked together. Used to render only the outputs affected by a change.
+#[derive(Default)]
+pub struct Dependencies {
+    pages: HashMap<Box<str>, Node>,
+    taxa: HashMap<Box<str>, Box<[PathBuf]>>,
+}
+
+/// One page as it was in the last build.
+struct Node {
+    fingerprint: u64,
+    excluded: bool,
+    /// Hash of the links to the translations of the page.
+    translations: u64,
+    parent: Box<str>,
+    previous: Box<str>,
+    next: Box<str>,
+    /// Identifiers of all the taxonomy keys this page was classified into.
+    taxa: Box<[Box<str>]>,
+    outputs: Box<[PathBuf]>,
+}
+
+/// Name of the taxonomy, its key (`None` for the taxonomy itself)
+/// and the outputs rendered from it (`None` if it was not rendered).
+type TaxonOutputs<'a> = (&'a str, Option<&'a str>, Option<Vec<PathBuf>>);
+
+/// Pages and taxonomies that need to be rendered again.
+pub struct Affected {
+    pages: Vec<bool>,
+    taxa: HashSet<Box<str>>,
+    all: bool,
+}
+
+impl Dependencies {
+    /// Compare the pages with the last build and determine what needs to be rendered.
+    /// Everything is affected if there was no previous build, or if a change shows on every
+    /// page: that of the index or of its direct children, or a new or removed taxonomy key.
+    pub fn affected(
+        &self,
+        pages: &[Page<'_>],
+        classification: &Classification<'_, '_>,
+    ) -> Affected {
+        let len = pages.len();
+        let mut affected = Affected {
+            pages: vec![false; len],
+            taxa: HashSet::new(),
+            all: self.pages.is_empty(),
+        };
+        if affected.all || self.taxa_changed(classification) {
+            affected.all = true;
+            return affected;
+        }
+
+        let keys: Vec<Box<str>> = pages.iter().map(key).collect();
+        let neighbour = |i: usize| if i == 0 { "" } else { keys[i].as_ref() };
+        let mut changed = Vec::new();
+        for (i, page) in pages.iter().enumerate() {
+            let same = self.pages.get(&keys[i]).filter(|node| {
+                page.fingerprint != 0
+                    && node.fingerprint == page.fingerprint
+                    && node.excluded == page.excluded
+                    && node.translations == translations_hash(page)
+                    && *node.parent == *keys[page.parent]
+                    && *node.previous == *neighbour(page.previous)
+                    && *node.next == *neighbour(page.next)
+            });
+            match same {
+                Some(_) => {}
+                None if i == 0 || page.parent == 0 => {
+                    // Every page links to the index and its subpages and subsections.
+                    affected.all = true;
+                    return affected;
+                }
+                None => changed.push(i),
+            }
+        }
+
+        let index: HashMap<&str, usize> = HashMap(
+            keys.iter()
+                .enumerate()
+                .map(|(i, k)| (k.as_ref(), i))
+                .collect(),
+        );
+        // Pages that were removed affect what they were linked with.
+        let current: HashSet<&str> = keys.iter().map(AsRef::as_ref).collect();
+        for (key, node) in self.pages.iter() {
+            if !current.contains(key.as_ref()) {
+                if node.parent == keys[0] {
+                    affected.all = true;
+                    return affected;
+                }
+                for linked in [&node.parent, &node.previous, &node.next] {
+                    if let Some(&i) = index.get(linked.as_ref()) {
+                        affected.mark_with_ancestors(pages, i);
+                    }
+                }
+                affected.taxa.extend(node.taxa.iter().cloned());
+            }
+        }
+
+        for i in changed {
+            let page = &pages[i];
+            affected.mark_with_ancestors(pages, i);
+            if page.previous != 0 {
+                affected.pages[page.previous] = true;
+            }
+            if page.next != 0 {
+                affected.pages[page.next] = true;
+            }
+            // Subpages and subsections display their parent.
+            for j in page.pages.clone().chain(page.subsections.clone()) {
+                affected.pages[j] = true;
+            }
+            if let Some(node) = self.pages.get(&keys[i]) {
+                affected.taxa.extend(node.taxa.iter().cloned());
+            }
+            affected.taxa.extend(classified(page, classification));
+        }
+        affected
+    }
+
+    /// Record the outputs of the current build. Outputs of pages and taxonomies that were not
+    /// affected (`None`) are taken over from the previous build.
+    pub fn update(
+        &mut self,
+        pages: &[Page<'_>],
+        classification: &Classification<'_, '_>,
+        outputs: Vec<Option<Vec<PathBuf>>>,
+        taxa: Vec<TaxonOutputs>,
+    ) {
+        let keys: Vec<Box<str>> = pages.iter().map(key).collect();
+        let neighbour = |i: usize| -> Box<str> {
+            if i == 0 {
+                "".into()
+            } else {
+                keys[i].clone()
+            }
+        };
+        let mut old = std::mem::take(&mut self.pages);
+        for ((page, key), rendered) in pages.iter().zip(keys.iter()).zip(outputs) {
+            let outputs = match rendered {
+                Some(rendered) => rendered.into(),
+                None => old.remove(key).map(|node| node.outputs).unwrap_or_default(),
+            };
+            let node = Node {
+                fingerprint: page.fingerprint,
+                excluded: page.excluded,
+                translations: translations_hash(page),
+                parent: keys[page.parent].clone(),
+                previous: neighbour(page.previous),
+                next: neighbour(page.next),
+                taxa: classified(page, classification).collect(),
+                outputs,
+            };
+            self.pages.insert(key.clone(), node);
+        }
+
+        let mut old = std::mem::take(&mut self.taxa);
+        for (name, taxon, rendered) in taxa {
+            let id = taxon_id(name, taxon);
+            let outputs = match rendered {
+                Some(rendered) => rendered.into(),
+                None => old.remove(&id).unwrap_or_default(),
+            };
+            self.taxa.insert(id, outputs);
+        }
+    }
+
+    /// Are there taxonomies or their keys that were not in the last build, or the other way?
+    fn taxa_changed(&self, classification: &Classification<'_, '_>) -> bool {
+        let mut count = 0;
+        for (&name, taxonomy) in classification.iter() {
+            count += 1 + taxonomy.keys().len();
+            let keys = taxonomy.keys().keys().map(|&key| Some(key));
+            if std::iter::once(None)
+                .chain(keys)
+                .any(|key| !self.taxa.contains_key(&taxon_id(name, key)))
+            {
+                return true;
+            }
+        }
+        count != self.taxa.len()
+    }
+
+    /// All the paths that were rendered from pages and taxonomies.
+    pub fn outputs(&self) -> impl Iterator<Item = &PathBuf> {
+        self.pages
+            .values()
+            .flat_map(|node| node.outputs.iter())
+            .chain(self.taxa.values().flat_map(|outputs| outputs.iter()))
+    }
+}
+
+impl Affected {
+    /// Does the page with the given index need to be rendered again?
+    #[inline]
+    pub fn page(&self, i: usize) -> bool {
+        self.all || self.pages.get(i).copied().unwrap_or(true)
+    }
+
+    /// Does the given taxonomy (with `None`), or its key, need to be rendered again?
+    #[inline]
+    pub fn taxon(&self, name: &str, key: Option<&str>) -> bool {
+        self.all
+            || match key {
+                Some(_) => self.taxa.contains(&taxon_id(name, key)),
+                // The taxonomy page lists all of its keys.
+                None => self.taxa.iter().any(|id| {
+                    id.strip_prefix(name)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                }),
+            }
+    }
+
+    #[inline]
+    fn mark_with_ancestors(&mut self, pages: &[Page<'_>], mut i: usize) {
+        self.pages[i] = true;
+        while i != 0 {
+            i = pages[i].parent;
+            self.pages[i] = true;
+        }
+    }
+}
+
+/// The identifier of a page that stays the same between builds.
+#[inline]
+fn key(page: &Page<'_>) -> Box<str> {
+    let sep = if page.is_section { "/" } else { "" };
+    format!("{}/{}{}", page.path.as_ref(), page.slug, sep).into()
+}
+
+#[inline]
+fn translations_hash(page: &Page<'_>) -> u64 {
+    let mut hasher = FnvHasher::default();
+    for translation in page.translations.iter() {
+        hasher.write(translation.lang.as_bytes());
+        hasher.write(translation.permalink.as_bytes());
+    }
+    hasher.finish()
+}
+
+#[inline]
+fn taxon_id(name: &str, key: Option<&str>) -> Box<str> {
+    match key {
+        Some(key) => format!("{}/{}", name, key).into(),
+        None => name.into(),
+    }
+}
+
+/// Identifiers of all the taxonomy keys the page is classified into.
+#[inline]
+fn classified<'a>(
+    page: &'a Page<'_>,
+    classification: &'a Classification<'_, '_>,
+) -> impl Iterator<Item = Box<str>> + 'a {
+    page.taxonomies
+        .iter()
+        .filter(move |(name, _)| classification.contains_key(*name))
+        .flat_map(|(name, family)| family.iter().map(move |s| taxon_id(name, Some(&**s))))
+}
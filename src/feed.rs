@@ -0,0 +1,149 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::page::{Page, Permalink};
+use crate::search::write_json_str;
+use crate::site::Site;
+use crate::types::{Any, DateTime};
+
+use std::io::{self, Write};
+
+/// Write the JSON Feed (version 1.1) of the pages into the buffer, for the feed located
+/// in the directory at `path` relative to the site, empty for the root.
+/// Every page has its full rendered content (or just the summary or excerpt, if set so),
+/// summary, image, author, if given, date and tags made of all its taxonomies.
+pub(crate) fn json_feed(
+    pages: &[&Page<'_>],
+    site: &Site<'_>,
+    path: &str,
+    buffer: &mut Vec<u8>,
+) -> io::Result<()> {
+    let url = site.pages_url().trim_end_matches('/');
+    let home = match path.trim_matches('/') {
+        "" => format!("{}/", url),
+        path => format!("{}/{}/", url, path),
+    };
+    buffer.extend_from_slice(b"{\"version\":\"https://jsonfeed.org/version/1.1\",\"title\":");
+    write_json_str(buffer, &site.title)?;
+    buffer.extend_from_slice(b",\"home_page_url\":");
+    write_json_str(buffer, &home)?;
+    buffer.extend_from_slice(b",\"feed_url\":");
+    write_json_str(buffer, &format!("{}feed.json", home))?;
+    write_field(buffer, "description", &site.description)?;
+    write_field(buffer, "language", &site.lang)?;
+    write_authors(buffer, &site.author)?;
+
+    buffer.extend_from_slice(b",\"items\":[");
+    for (i, page) in pages.iter().enumerate() {
+        if i > 0 {
+            buffer.push(b',');
+        }
+        let permalink = Permalink(page, url).to_string();
+        buffer.extend_from_slice(b"{\"id\":");
+        write_json_str(buffer, &permalink)?;
+        buffer.extend_from_slice(b",\"url\":");
+        write_json_str(buffer, &permalink)?;
+        write_field(buffer, "title", &page.title)?;
+        if site.feed.summary_only && !page.summary.is_empty() {
+            buffer.extend_from_slice(b",\"content_text\":");
+            write_json_str(buffer, &page.summary)?;
+        } else if site.feed.summary_only {
+            buffer.extend_from_slice(b",\"content_html\":");
+            write_json_str(buffer, &page.summary_html())?;
+        } else {
+            buffer.extend_from_slice(b",\"content_html\":");
+            write_json_str(buffer, page.content_html())?;
+        }
+        write_field(buffer, "summary", page.summary_text())?;
+        if !page.image.is_empty() {
+            write_field(buffer, "image", &absolute(&page.image, url))?;
+        }
+        if let Some(date) = page.date {
+            write_field(buffer, "date_published", &rfc3339(date))?;
+        }
+        if let Some(Any::String(author)) = page.extra.get("author") {
+            write_authors(buffer, author)?;
+        }
+
+        let mut tags: Vec<&str> = page
+            .taxonomies
+            .values()
+            .flat_map(|family| family.iter().map(|species| &**species))
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        if !tags.is_empty() {
+            buffer.extend_from_slice(b",\"tags\":[");
+            for (j, tag) in tags.into_iter().enumerate() {
+                if j > 0 {
+                    buffer.push(b',');
+                }
+                write_json_str(buffer, tag)?;
+            }
+            buffer.push(b']');
+        }
+        buffer.push(b'}');
+    }
+    buffer.extend_from_slice(b"]}");
+    Ok(())
+}
+
+/// Write the `"name":"value"` pair preceded by a comma, unless the value is empty.
+fn write_field(buffer: &mut Vec<u8>, name: &str, value: &str) -> io::Result<()> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    write!(buffer, ",\"{}\":", name)?;
+    write_json_str(buffer, value)
+}
+
+fn write_authors(buffer: &mut Vec<u8>, name: &str) -> io::Result<()> {
+    if name.is_empty() {
+        return Ok(());
+    }
+    buffer.extend_from_slice(b",\"authors\":[{\"name\":");
+    write_json_str(buffer, name)?;
+    buffer.extend_from_slice(b"}]");
+    Ok(())
+}
+
+/// The link, prefixed with the site URL if it's not a full URL already.
+fn absolute(link: &str, url: &str) -> String {
+    if link.contains("://") {
+        link.to_string()
+    } else {
+        format!("{}/{}", url, link.trim_start_matches('/'))
+    }
+}
+
+fn rfc3339(date: DateTime) -> String {
+    date.0.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn summary_only_excerpt() {
+        let mut page = Page::default();
+        page.title = "Post".into();
+        page.content = "Some *emphasis*.\n\n<!-- more -->\n\nThe rest.".into();
+        page.summarize(1, 0);
+        let mut site = Site::default();
+        site.feed.summary_only = true;
+
+        let mut buffer = Vec::new();
+        json_feed(&[&page], &site, "", &mut buffer).unwrap();
+        let feed: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        let item = &feed["items"][0];
+        assert_eq!(item["content_html"], "<p>Some <em>emphasis</em>.</p>\n");
+        assert_eq!(item["summary"], "Some emphasis.");
+        assert!(item.get("content_text").is_none());
+    }
+}
@@ -0,0 +1,448 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::{Config, Error, Unwind, ASSET_SRC_DIR};
+use blades::{Page, Picture, Variants};
+
+use beef::lean::Cow;
+use fnv::FnvHasher;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use serde::Deserialize;
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufWriter, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// Where the processed images are cached, relative to the site directory.
+static IMAGE_CACHE_DIR: &str = ".blades.images";
+/// Where the processed images are placed, relative to the output directory.
+static PROCESSED_DIR: &str = "processed_images";
+
+/// Settings of the image processing.
+#[derive(Deserialize)]
+pub(crate) struct ImageConfig<'c> {
+    /// Widths of the resized versions of every image, used in `srcset`.
+    #[serde(default)]
+    widths: Box<[u32]>,
+    /// Size of the square the thumbnails fit into, 0 for no thumbnails.
+    #[serde(default)]
+    thumbnail: u32,
+    /// Format of the processed images: "webp", "jpeg" or "png",
+    /// or empty to keep the format of the original.
+    #[serde(borrow, default)]
+    format: Cow<'c, str>,
+    /// Quality of the JPEG images, from 1 to 100.
+    #[serde(default = "default_quality")]
+    quality: u8,
+}
+
+#[inline]
+const fn default_quality() -> u8 {
+    80
+}
+
+/// An image being processed.
+struct Source {
+    path: PathBuf,
+    data: Vec<u8>,
+    /// Hash of the data and the settings of the processing
+    hash: u64,
+    format: ImageFormat,
+    /// The image, once it was decoded
+    decoded: Option<DynamicImage>,
+}
+
+/// Finds the images of the pages of one language by their links.
+pub(crate) struct Locator<'a> {
+    url: &'a str,
+    /// The output directory of the whole site
+    output_dir: &'a Path,
+    /// The content and the output directories of the language
+    content_dir: &'a str,
+    language_dir: &'a Path,
+    /// The asset directories of the site and of the theme
+    asset_dirs: Vec<PathBuf>,
+    /// The subdirectory of the output directory the assets are placed into
+    assets: &'a str,
+}
+
+impl<'a> Locator<'a> {
+    /// Create a locator for the language with the given content and output directories.
+    pub(crate) fn new(
+        config: &'a Config<'a>,
+        content_dir: &'a str,
+        language_dir: &'a Path,
+    ) -> Self {
+        let mut asset_dirs = vec![PathBuf::from(ASSET_SRC_DIR)];
+        if !config.theme.is_empty() {
+            let mut theme_assets = Path::new(config.theme_dir.as_ref()).join(config.theme.as_ref());
+            theme_assets.push(ASSET_SRC_DIR);
+            asset_dirs.push(theme_assets);
+        }
+        Self {
+            url: &config.site.url,
+            output_dir: Path::new(config.output_dir.as_ref()),
+            content_dir,
+            language_dir,
+            asset_dirs,
+            assets: &config.site.assets,
+        }
+    }
+
+    /// Find the file of the image with the given link on the page.
+    /// It can be among the content, the assets or already in the output directory.
+    #[inline]
+    pub(crate) fn find(&self, link: &str, page: &Page<'_>) -> Option<PathBuf> {
+        self.locate(link, page, Path::is_file)
+    }
+
+    /// Find the directory with the given link on the page, in the same places as `find`.
+    #[inline]
+    pub(crate) fn find_dir(&self, link: &str, page: &Page<'_>) -> Option<PathBuf> {
+        self.locate(link, page, Path::is_dir)
+    }
+
+    fn locate(&self, link: &str, page: &Page<'_>, exists: fn(&Path) -> bool) -> Option<PathBuf> {
+        let link = link
+            .strip_prefix(self.url)
+            .filter(|_| !self.url.is_empty())
+            .unwrap_or(link);
+        if link.starts_with("//") || link.contains("://") {
+            return None;
+        }
+        let relative = match link.strip_prefix('/') {
+            Some(link) => PathBuf::from(link),
+            None => {
+                // Relative to the directory the page is rendered into
+                let mut dir = PathBuf::from(page.path.as_ref());
+                if page.is_section {
+                    dir.push(page.slug.as_ref());
+                }
+                dir.join(link)
+            }
+        };
+        let dirs = [
+            Path::new(self.content_dir),
+            self.language_dir,
+            self.output_dir,
+        ];
+        let found = dirs
+            .iter()
+            .map(|dir| dir.join(&relative))
+            .find(|path| exists(path));
+        if found.is_some() {
+            return found;
+        }
+        let assets = relative.strip_prefix(self.assets).ok()?;
+        let mut candidates = self.asset_dirs.iter().map(|dir| dir.join(assets));
+        candidates.find(|path| exists(path))
+    }
+}
+
+/// Fill in the gallery of the page with the images from its `pictures_dir`, sorted by their names.
+/// The pictures listed on the page come first and are matched with the images by the file
+/// names, so they can give them captions or alternative texts. Pictures without `pid` get
+/// the file name without the extension. The listing of the directory, if there is one,
+/// is added to the fingerprint of the page.
+pub(crate) fn fill_gallery(locator: &Locator<'_>, page: &mut Page<'_>) -> Result<(), Error> {
+    const EXTENSIONS: [&str; 9] = [
+        "jpg", "jpeg", "png", "gif", "webp", "avif", "tif", "tiff", "svg",
+    ];
+
+    let file_name = |file: &str| file.rsplit('/').next().unwrap_or_default().to_string();
+    let dir = page.pictures_dir.trim_end_matches('/');
+    let mut files = Vec::new();
+    if let Some(path) = Some(dir)
+        .filter(|d| !d.is_empty())
+        .and_then(|d| locator.find_dir(d, page))
+    {
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            let extension = path.extension().unwrap_or_default().to_string_lossy();
+            if path.is_file() && EXTENSIONS.contains(&extension.to_lowercase().as_str()) {
+                files.push(
+                    path.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned(),
+                );
+            }
+        }
+        files.sort_unstable();
+    }
+    let listing = files.join("\n");
+
+    let mut pictures = std::mem::take(&mut page.pictures).into_vec();
+    for picture in pictures.iter_mut() {
+        let name = file_name(&picture.file);
+        if let Some(i) = files.iter().position(|file| *file == name) {
+            picture.file = format!("{}/{}", dir, files.remove(i)).into();
+        }
+    }
+    pictures.extend(files.into_iter().map(|file| Picture {
+        file: format!("{}/{}", dir, file).into(),
+        ..Picture::default()
+    }));
+    for picture in pictures.iter_mut().filter(|picture| picture.pid.is_empty()) {
+        let name = file_name(&picture.file);
+        let stem = name
+            .rsplit_once('.')
+            .map_or(name.as_str(), |(stem, _)| stem);
+        picture.pid = stem.to_string().into();
+    }
+    page.pictures = pictures.into();
+    // The page has to be rendered again when the images in the directory change
+    if !dir.is_empty() {
+        page.add_to_fingerprint(listing.as_bytes());
+    }
+    Ok(())
+}
+
+/// Processes the images of the pages of one language.
+pub(crate) struct Processor<'a> {
+    settings: &'a ImageConfig<'a>,
+    format: Option<ImageFormat>,
+    /// The URL of the whole site, where the processed images are placed.
+    url: &'a str,
+    /// The output directory of the whole site
+    output_dir: &'a Path,
+    locator: &'a Locator<'a>,
+}
+
+impl<'a> Processor<'a> {
+    /// Create a processor finding the images with the given locator.
+    pub(crate) fn new(
+        config: &'a Config<'a>,
+        settings: &'a ImageConfig<'a>,
+        locator: &'a Locator<'a>,
+    ) -> Result<Self, Error> {
+        let format = match settings.format.as_ref() {
+            "" => None,
+            "webp" => Some(ImageFormat::WebP),
+            "jpeg" | "jpg" => Some(ImageFormat::Jpeg),
+            "png" => Some(ImageFormat::Png),
+            other => return Err(Error::ImageFormat(other.into())),
+        };
+        Ok(Self {
+            settings,
+            format,
+            url: &config.site.url,
+            output_dir: Path::new(config.output_dir.as_ref()),
+            locator,
+        })
+    }
+
+    /// Process the images of all the pages and their pictures in parallel, filling in their
+    /// variants. Return the paths of the processed images in the output directory.
+    pub(crate) fn process(
+        &self,
+        pages: &mut [Page<'_>],
+        per_thread: usize,
+    ) -> Result<Vec<PathBuf>, Error> {
+        fs::create_dir_all(IMAGE_CACHE_DIR)?;
+        fs::create_dir_all(self.output_dir.join(PROCESSED_DIR))?;
+        thread::scope(|s| {
+            let threads: Vec<_> = pages
+                .chunks_mut(per_thread)
+                .map(|chunk| s.spawn(move || self.process_chunk(chunk)))
+                .collect();
+            let mut outputs = Vec::new();
+            for thread in threads {
+                outputs.append(&mut thread.join().unwind()?);
+            }
+            Ok(outputs)
+        })
+    }
+
+    fn process_chunk(&self, pages: &mut [Page<'_>]) -> Result<Vec<PathBuf>, Error> {
+        let mut outputs = Vec::new();
+        for page in pages.iter_mut() {
+            let image = Some(&page.image).filter(|image| !image.is_empty());
+            if let Some(path) = image.and_then(|image| self.find(image, page)) {
+                let (variants, hash) = self.variants(path, &page.image, &mut outputs)?;
+                page.image_variants = Some(variants);
+                page.add_to_fingerprint(&hash.to_le_bytes());
+            }
+            for i in 0..page.pictures.len() {
+                let file = &page.pictures[i].file;
+                if let Some(path) = self.find(file, page) {
+                    let (variants, hash) = self.variants(path, file, &mut outputs)?;
+                    page.pictures[i].variants = variants;
+                    page.add_to_fingerprint(&hash.to_le_bytes());
+                }
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Find the file of the image with the given link on the page, if it can be decoded.
+    /// Other images, such as SVG or GIF, are used as they are.
+    fn find(&self, link: &str, page: &Page<'_>) -> Option<PathBuf> {
+        let path = self.locator.find(link, page)?;
+        let format = ImageFormat::from_path(&path);
+        matches!(
+            format,
+            Ok(ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)
+        )
+        .then_some(path)
+    }
+
+    /// Produce the thumbnail and the resized versions of the image with the given path
+    /// and link, unless they are cached,
+    /// and place them into the output directory, adding their paths to `outputs`.
+    /// Return them with the hash their names are made of, which the page depends on.
+    fn variants(
+        &self,
+        path: PathBuf,
+        link: &str,
+        outputs: &mut Vec<PathBuf>,
+    ) -> Result<(Variants<'static>, u64), Error> {
+        let (width, height) = image::image_dimensions(&path).map_err(|e| image_error(e, &path))?;
+        let format = match self.format {
+            Some(format) => format,
+            None => ImageFormat::from_path(&path).map_err(|e| image_error(e, &path))?,
+        };
+        let data = fs::read(&path)?;
+        let mut hasher = FnvHasher::default();
+        hasher.write(&data);
+        hasher.write_u8(self.settings.quality);
+        hasher.write(format.extensions_str()[0].as_bytes());
+        let mut source = Source {
+            hash: hasher.finish(),
+            path,
+            data,
+            format,
+            decoded: None,
+        };
+
+        let mut srcset = String::new();
+        for &w in self.settings.widths.iter().filter(|&&w| w < width) {
+            let resize = |image: &DynamicImage, w| image.resize(w, u32::MAX, FilterType::Lanczos3);
+            let resized = self.variant(&mut source, "w", w, resize, outputs)?;
+            srcset.push_str(&format!("{} {}w, ", resized, w));
+        }
+        if !srcset.is_empty() {
+            srcset.push_str(&format!("{} {}w", link, width));
+        }
+        let thumbnail = match self.settings.thumbnail {
+            0 => String::new(),
+            t => self.variant(
+                &mut source,
+                "thumb",
+                t,
+                |image, t| image.thumbnail(t, t),
+                outputs,
+            )?,
+        };
+
+        let variants = Variants {
+            width,
+            height,
+            thumbnail: thumbnail.into(),
+            srcset: srcset.into(),
+        };
+        Ok((variants, source.hash))
+    }
+
+    /// Produce one variant of the image, named by the hash of the source and the parameters,
+    /// unless it's cached, and place it into the output directory. Return the link to it.
+    fn variant(
+        &self,
+        source: &mut Source,
+        kind: &str,
+        size: u32,
+        resize: fn(&DynamicImage, u32) -> DynamicImage,
+        outputs: &mut Vec<PathBuf>,
+    ) -> Result<String, Error> {
+        let mut hasher = FnvHasher::default();
+        hasher.write_u64(source.hash);
+        hasher.write(kind.as_bytes());
+        hasher.write_u32(size);
+        let stem = source
+            .path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let extension = source.format.extensions_str()[0];
+        let name = format!(
+            "{}-{}{}-{:016x}.{}",
+            stem,
+            kind,
+            size,
+            hasher.finish(),
+            extension
+        );
+
+        let cached = Path::new(IMAGE_CACHE_DIR).join(&name);
+        if !cached.exists() {
+            let error = |e| image_error(e, &source.path);
+            let decoded = match source.decoded.take() {
+                Some(decoded) => decoded,
+                None => image::load_from_memory(&source.data).map_err(error)?,
+            };
+            let image = resize(&decoded, size);
+            self.encode(&image, source.format, &cached).map_err(error)?;
+            source.decoded = Some(decoded);
+        }
+        let output = self.output_dir.join(PROCESSED_DIR).join(&name);
+        if !output.exists() {
+            fs::copy(&cached, &output)?;
+        }
+        outputs.push(output);
+        Ok(format!("{}/{}/{}", self.url, PROCESSED_DIR, name))
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        format: ImageFormat,
+        path: &Path,
+    ) -> image::ImageResult<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            ImageFormat::Jpeg => {
+                let encoder = JpegEncoder::new_with_quality(&mut writer, self.settings.quality);
+                DynamicImage::from(image.to_rgb8()).write_with_encoder(encoder)
+            }
+            ImageFormat::WebP => DynamicImage::from(image.to_rgba8()).write_to(&mut writer, format),
+            _ => image.write_to(&mut writer, format),
+        }
+    }
+}
+
+/// Delete the cached images that were not produced in the current build, so the cache doesn't
+/// keep the variants of removed or changed images or of other settings.
+/// `processed` are the paths of all the processed images in the output directory.
+pub(crate) fn prune_cache(processed: &[PathBuf]) -> Result<(), io::Error> {
+    let used: BTreeSet<&OsStr> = processed
+        .iter()
+        .filter_map(|path| path.file_name())
+        .collect();
+    let entries = match fs::read_dir(IMAGE_CACHE_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if !used.contains(entry.file_name().as_os_str()) {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+fn image_error(e: image::ImageError, path: &Path) -> Error {
+    Error::Image(e, path.to_string_lossy().into())
+}
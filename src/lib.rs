@@ -16,16 +16,22 @@
 //! the `bin` feature gate, which is enabled by default. When using Blades as a library, they are not
 //! necessary, so it is recommended to import blades with `default_features = false`.
 #![warn(missing_docs)]
+mod dependencies;
+mod feed;
+mod minify;
 mod page;
 mod render;
+mod search;
 mod site;
+mod sitemap;
 mod sources;
 mod taxonomies;
 mod types;
 
-pub use page::{Context, Page, Pages, Picture};
-pub use render::render_meta;
-pub use site::Site;
+pub use dependencies::{Affected, Dependencies};
+pub use page::{Context, Exif, Page, Pages, Picture, Translation, Variants};
+pub use render::{add_meta_templates, render_meta, BuildCache, Rendered};
+pub use site::{FeedConfig, Site};
 pub use sources::{Parser, Source, Sources};
 pub use taxonomies::{TaxonMeta, Taxonomies, Taxonomy};
 pub use types::{Ancestors, Any, DateTime, HashMap};
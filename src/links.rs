@@ -0,0 +1,111 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::percent_decode;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Find all the internal links in the HTML files in the output directory that point
+/// to files which don't exist there. `url` is the URL of the site.
+/// Returns the pairs of the file containing the link and the link.
+pub(crate) fn broken_links(
+    output_dir: &Path,
+    url: &str,
+) -> Result<Vec<(PathBuf, String)>, io::Error> {
+    // The path of the site on its host, e.g. "/blog" for "https://example.com/blog/"
+    let base = url
+        .split_once("://")
+        .and_then(|(_, rest)| rest.find('/').map(|i| &rest[i..]))
+        .unwrap_or_default()
+        .trim_end_matches('/');
+    let url = url.trim_end_matches('/');
+    let mut files = Vec::new();
+    html_files(output_dir, &mut files)?;
+
+    let mut broken = Vec::new();
+    for file in files {
+        let html = fs::read_to_string(&file)?;
+        for link in links(&html) {
+            let (path, relative) = match link.strip_prefix(url).filter(|_| !url.is_empty()) {
+                Some(path) => (path, false),
+                None if link.starts_with(['#', '?']) || link.starts_with("//") => continue,
+                None if is_external(link) => continue,
+                None => match link.strip_prefix(base).filter(|_| link.starts_with('/')) {
+                    Some(path) => (path, false),
+                    None if link.starts_with('/') => continue,
+                    None => (link, true),
+                },
+            };
+            let path = percent_decode(path.split(['?', '#']).next().unwrap_or_default());
+            let mut target = if relative {
+                file.parent().unwrap_or(output_dir).join(&path)
+            } else {
+                output_dir.join(path.trim_start_matches('/'))
+            };
+            if target.is_dir() {
+                target.push("index.html");
+            }
+            if !target.exists() {
+                broken.push((file.clone(), link.to_string()));
+            }
+        }
+    }
+    Ok(broken)
+}
+
+fn html_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            html_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "html") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Values of all the `href` and `src` attributes in the HTML.
+fn links(html: &str) -> impl Iterator<Item = &str> {
+    let mut rest = html;
+    std::iter::from_fn(move || loop {
+        let i = rest.find(['h', 's'])?;
+        rest = &rest[i..];
+        let name = ["href=", "src="]
+            .into_iter()
+            .find(|name| rest.starts_with(name));
+        let Some(name) = name else {
+            rest = &rest[1..];
+            continue;
+        };
+        // The attribute name has to be preceded by a whitespace
+        let preceded = html[..html.len() - rest.len()].ends_with(char::is_whitespace);
+        rest = &rest[name.len()..];
+        let quote = match rest.chars().next() {
+            Some(q @ ('"' | '\'')) if preceded => q,
+            _ => continue,
+        };
+        rest = &rest[1..];
+        let end = rest.find(quote)?;
+        let value = &rest[..end];
+        rest = &rest[end..];
+        return Some(value.trim());
+    })
+}
+
+/// Does the link have a scheme, such as `https:` or `mailto:`?
+fn is_external(link: &str) -> bool {
+    link.split_once(':').is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
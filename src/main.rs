@@ -6,6 +6,15 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with Blades.  If not, see <http://www.gnu.org/licenses/>
+mod assets;
+mod compress;
+mod data;
+mod images;
+mod links;
+mod metadata;
+mod serve;
+mod watch;
+
 use blades::*;
 
 use beef::lean::Cow;
@@ -35,11 +44,17 @@ Commands:
   colocate  Move assets from the "assets" directory and from the theme, if one is used, into the output directory
   all       Build the site and colocate the assets
   lazy      Build the site and (colocate assets only if the theme was switched) [default]
+  watch     Build the site and render again what was affected whenever its sources change
+  serve     Watch the site and serve it on localhost, reloading it in the browser on changes
   help      Print this message
   version   Print version information
 
+Options:
+  --drafts  Render also drafts and pages outside of their publish and expiry dates
+
 Environment variables:
   BLADES_CONFIG   File to read the site config from [default: Blades.toml]
+  BLADES_PORT     Port to serve the site on locally [default: 8000]
 "#;
 static VAR_CONFIG: &str = "BLADES_CONFIG";
 static CONFIG_FILE: &str = "Blades.toml";
@@ -54,6 +69,8 @@ enum Cmd {
     Colocate,
     All,
     Lazy,
+    Watch,
+    Serve,
     Help,
     Version,
     Invalid,
@@ -81,6 +98,24 @@ struct Config<'c> {
     /// Generate taxonomies not specified in the config?
     #[serde(default = "default_true")]
     implicit_taxonomies: bool,
+    /// Other languages of the site, mapped to the directories of their content.
+    #[serde(borrow, default)]
+    languages: HashMap<&'c str, Cow<'c, str>>,
+    /// Check the rendered HTML for internal links to files that don't exist?
+    #[serde(default)]
+    check_links: bool,
+    /// Read the EXIF metadata of the gallery pictures?
+    #[serde(default)]
+    exif: bool,
+    /// Settings of the image processing, if the images should be processed.
+    #[serde(borrow, default)]
+    images: Option<images::ImageConfig<'c>>,
+    /// Add hashes of their contents to the names of the assets?
+    #[serde(default)]
+    fingerprint_assets: bool,
+    /// Settings of writing compressed variants of the files, if they should be written.
+    #[serde(default)]
+    compression: Option<compress::CompressConfig>,
 
     /// Information about the site usable in templates
     #[serde(flatten)]
@@ -137,6 +172,7 @@ static TEMPLATE_DIR: &str = "templates";
 /// Where the assets will be copied from, relative to the site directrory.
 static ASSET_SRC_DIR: &str = "assets";
 static FILELIST: &str = ".blades";
+static CACHE_FILE: &str = ".blades.cache";
 static OLD_THEME: &str = ".bladestheme";
 
 #[derive(Content)]
@@ -157,6 +193,8 @@ struct MockPage {
 enum Format {
     #[default]
     Toml,
+    Yaml,
+    Json,
     Markdown,
 }
 
@@ -164,6 +202,12 @@ enum Format {
 enum ParseError {
     #[error("TOML error: {0}")]
     Toml(#[from] toml::de::Error),
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("CSV error: {0}")]
+    Csv(&'static str),
     #[error("Invalid UTF8: {0}")]
     Utf8(#[from] std::str::Utf8Error),
 }
@@ -182,6 +226,20 @@ enum Error {
     Plugin(Box<str>, Box<str>),
     #[error("Plugin {0} returned invalid UTF8 data: {1}")]
     Utf8(Box<str>, std::string::FromUtf8Error),
+    #[error("Page {0} links to {1}, which does not exist")]
+    Link(Box<str>, Box<str>),
+    #[error("Found {0} broken links")]
+    BrokenLinks(usize),
+    #[error("Error processing image {1}: {0}")]
+    Image(image::ImageError, Box<str>),
+    #[error("Unknown image format {0}")]
+    ImageFormat(Box<str>),
+    #[error("Sass error in {0}, line {1}: {2}")]
+    Sass(Box<str>, usize, Box<str>),
+    #[error("Error minifying {0}: {1}")]
+    Minify(Box<str>, Box<str>),
+    #[error("Assets {0} and {1} have the same name in templates")]
+    AssetName(Box<str>, Box<str>),
 }
 
 impl From<(ParseError, Box<str>)> for Error {
@@ -215,6 +273,10 @@ impl Parser for Format {
     fn from_extension(ext: &OsStr) -> Option<Self> {
         if ext == "toml" {
             Some(Format::Toml)
+        } else if ext == "yaml" || ext == "yml" {
+            Some(Format::Yaml)
+        } else if ext == "json" {
+            Some(Format::Json)
         } else if ext == "md" {
             Some(Format::Markdown)
         } else {
@@ -226,9 +288,23 @@ impl Parser for Format {
     fn parse<'a>(&self, data: &'a [u8]) -> Result<Page<'a>, Self::Error> {
         Ok(match self {
             Format::Toml => toml::from_slice(data)?,
+            Format::Yaml => serde_yaml::from_slice(data)?,
+            Format::Json => serde_json::from_slice(data)?,
             Format::Markdown => {
-                let (header, content) = separate_md_header(data);
-                let mut page: Page = toml::from_slice(header)?;
+                let (mut page, content): (Page, _) = match separate_yaml_header(data) {
+                    // An empty header is parsed as TOML to get all the defaults
+                    Some((header, content)) if header.trim_ascii().is_empty() => {
+                        (toml::from_slice(header)?, content)
+                    }
+                    Some((header, content)) => (serde_yaml::from_slice(header)?, content),
+                    None => match separate_json_header(data)? {
+                        Some(separated) => separated,
+                        None => {
+                            let (header, content) = separate_md_header(data);
+                            (toml::from_slice(header)?, content)
+                        }
+                    },
+                };
                 let content = std::str::from_utf8(content)?;
                 page.content = content.trim().into();
                 page
@@ -274,6 +350,42 @@ fn separate_md_header(source: &[u8]) -> (&[u8], &[u8]) {
     (&source[3..], &[])
 }
 
+/// Separate a YAML header in `---` from the markdown file, if its first line is `---`.
+/// The header can also be ended by `...`, as a YAML document.
+#[inline]
+fn separate_yaml_header(source: &[u8]) -> Option<(&[u8], &[u8])> {
+    let is_delimiter = |line: &[u8], end: &[u8]| line.trim_ascii_end() == end;
+    let mut lines = source.split_inclusive(|&b| b == b'\n');
+    let start = match lines.next() {
+        Some(line) if is_delimiter(line, b"---") => line.len(),
+        _ => return None,
+    };
+
+    let mut end = start;
+    for line in lines {
+        if is_delimiter(line, b"---") || is_delimiter(line, b"...") {
+            return Some((&source[start..end], &source[end + line.len()..]));
+        }
+        end += line.len();
+    }
+    Some((&source[start..], &[]))
+}
+
+/// Parse a JSON object header of the markdown file and separate the rest.
+/// If the file doesn't start with one, it has no JSON header.
+#[inline]
+fn separate_json_header(source: &[u8]) -> Result<Option<(Page<'_>, &[u8])>, ParseError> {
+    if source.first() != Some(&b'{') {
+        return Ok(None);
+    }
+    let mut stream = serde_json::Deserializer::from_slice(source).into_iter();
+    match stream.next() {
+        Some(Ok(page)) => Ok(Some((page, &source[stream.byte_offset()..]))),
+        Some(Err(e)) if e.is_data() => Err(e.into()),
+        _ => Ok(None),
+    }
+}
+
 trait Unwind {
     type Value;
 
@@ -377,68 +489,103 @@ fn new_page(config: &Config) -> Result<(), Error> {
     Ok(())
 }
 
-fn copy_dir(src: &mut PathBuf, dest: &mut PathBuf) -> Result<(), io::Error> {
-    let iter = match fs::read_dir(&src) {
-        Ok(iter) => iter,
-        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
-        Err(e) => return Err(e),
+/// Copy the files from the content directory that are not pages into the same places
+/// in the output directory, next to the pages, unless they are there already.
+/// Return the paths they were copied to.
+fn copy_bundled(
+    assets: &[PathBuf],
+    content_dir: &str,
+    output_dir: &Path,
+) -> Result<Vec<PathBuf>, io::Error> {
+    let mut copied = Vec::with_capacity(assets.len());
+    for asset in assets {
+        let dest = output_dir.join(asset.strip_prefix(content_dir).unwrap_or(asset));
+        copy_if_changed(asset, &dest)?;
+        copied.push(dest);
+    }
+    Ok(copied)
+}
+
+/// Copy the file, unless the destination is already there, with the same size and not older.
+fn copy_if_changed(src: &Path, dest: &Path) -> Result<(), io::Error> {
+    let src_meta = fs::metadata(src)?;
+    let outdated = match fs::metadata(dest) {
+        Ok(dest) => dest.len() != src_meta.len() || dest.modified()? < src_meta.modified()?,
+        Err(_) => true,
     };
-    fs::create_dir_all(&dest)?;
-    for entry in iter.filter_map(Result::ok) {
-        let file_type = entry.file_type()?;
-        let file_name = entry.file_name();
-        src.push(&file_name);
-        dest.push(&file_name);
-        if file_type.is_file() {
-            fs::copy(&src, &dest)?;
-        } else if file_type.is_dir() {
-            copy_dir(src, dest)?;
-        }
-        src.pop();
-        dest.pop();
+    if outdated {
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(src, dest)?;
     }
     Ok(())
 }
 
-/// Place assets located in the `assets` directory or in the `assets` subdirectory of the theme,
-/// if used, into a dedicated subdirectory of the output directory specified in the config
-/// (defaults to `assets`, too).
-fn colocate_assets(config: &Config) -> Result<(), io::Error> {
-    let mut output = Path::new(config.output_dir.as_ref()).join(config.site.assets.as_ref());
-    match fs::remove_dir_all(&output) {
-        Ok(_) => Ok(()),
-        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
-        Err(e) => Err(e),
-    }?;
-    let mut src = PathBuf::with_capacity(64);
-    if !config.theme.is_empty() {
-        src.push(config.theme_dir.as_ref());
-        src.push(config.theme.as_ref());
-        src.push(ASSET_SRC_DIR);
-        copy_dir(&mut src, &mut output)?;
-        src.clear();
+/// Decode the `%` escapes in a URL path; invalid escapes are kept as they are.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
     }
-    src.push(ASSET_SRC_DIR);
-    copy_dir(&mut src, &mut output)
+    String::from_utf8_lossy(&decoded).into_owned()
 }
 
 /// Load the templates from the directories specified by the config.
+/// Besides the HTML ones, these are the templates of the sitemap and the feeds,
+/// which replace the built-in ones, and of the other meta files listed in the config.
 fn load_templates(config: &Config) -> Result<Ramhorns, ramhorns::Error> {
+    let mut extensions = vec!["xml"];
+    for name in config.site.meta_outputs.iter() {
+        if let Some(extension) = Path::new(name.as_ref()).extension().and_then(OsStr::to_str) {
+            if extension != "html" && !extensions.contains(&extension) {
+                extensions.push(extension);
+            }
+        }
+    }
+
     fs::create_dir_all(TEMPLATE_DIR)?;
     let mut templates = Ramhorns::from_folder(TEMPLATE_DIR)?;
+    for extension in extensions.iter() {
+        templates.extend_from_folder_with_extension(TEMPLATE_DIR, extension)?;
+    }
     if !config.theme.is_empty() {
         let mut theme_path = Path::new(config.theme_dir.as_ref()).join(config.theme.as_ref());
         theme_path.push(TEMPLATE_DIR);
         if theme_path.exists() {
-            templates.extend_from_folder(theme_path)?;
+            templates.extend_from_folder(&theme_path)?;
+            for extension in extensions.iter() {
+                templates.extend_from_folder_with_extension(&theme_path, extension)?;
+            }
         }
     }
+    add_meta_templates(&mut templates)?;
     Ok(templates)
 }
 
-/// Delete all the pages that were present in the previous render, but not the current one.
-/// Then, write all the paths that were rendered to the file `filelist`
-fn cleanup(mut rendered: Vec<PathBuf>, filelist: &str) -> Result<(), io::Error> {
+/// Delete the files rendered in the previous build that were not rendered now,
+/// forgetting them in the `cache`, and write all the rendered paths to the file `filelist`.
+/// Return the number of deleted files.
+fn cleanup(
+    mut rendered: Vec<PathBuf>,
+    filelist: &str,
+    cache: &mut BuildCache,
+) -> Result<usize, io::Error> {
     let mut set = BTreeSet::new();
     for path in rendered.drain(..) {
         if let Some(p) = set.replace(path) {
@@ -446,10 +593,12 @@ fn cleanup(mut rendered: Vec<PathBuf>, filelist: &str) -> Result<(), io::Error>
         }
     }
 
+    let mut deleted = 0;
     if let Ok(f) = File::open(filelist) {
         BufReader::new(f).lines().try_for_each(|filename| {
             let filename = filename?;
             if !set.contains(Path::new(&filename)) {
+                deleted += 1;
                 // Every directory has its index rendered
                 if let Some(dir) = filename.strip_suffix("index.html") {
                     if dir.ends_with(path::is_separator) {
@@ -470,16 +619,17 @@ fn cleanup(mut rendered: Vec<PathBuf>, filelist: &str) -> Result<(), io::Error>
             }
         })?;
     };
+    cache.retain(|path| set.contains(path));
 
     let f = File::create(filelist)?;
     let mut f = BufWriter::new(f);
-    for path in rendered.drain(..) {
+    for path in set {
         // It was already checked that the paths contain valid UTF-8
         let path = path.into_os_string().into_string().unwrap();
         writeln!(&mut f, "{}", path)?;
     }
 
-    Ok(())
+    Ok(deleted)
 }
 
 /// A helper trait to print the name of the file that was not found.
@@ -500,12 +650,67 @@ impl<T> PrintIfNotFound for io::Result<T> {
     }
 }
 
+/// One language of the site: where its content is, the site info and where to render it.
+struct Language<'c> {
+    content_dir: &'c str,
+    site: Site<'c>,
+    output_dir: PathBuf,
+}
+
+/// The language of the main content first, followed by the other languages of the site,
+/// each rendered into a subdirectory named by it.
+fn languages<'c>(config: &'c Config) -> Vec<Language<'c>> {
+    let mut languages = Vec::with_capacity(config.languages.len() + 1);
+    let mut site = config.site.clone();
+    site.lang_url = site.url.trim_end_matches('/').to_string().into();
+    languages.push(Language {
+        content_dir: config.content_dir.as_ref(),
+        site,
+        output_dir: PathBuf::from(config.output_dir.as_ref()),
+    });
+    let mut other: Vec<_> = config.languages.iter().collect();
+    other.sort_unstable_by_key(|(&lang, _)| lang);
+    for (&lang, content_dir) in other {
+        let mut site = config.site.clone();
+        site.lang_url = format!("{}/{}", config.site.url.trim_end_matches('/'), lang).into();
+        site.lang = lang.into();
+        languages.push(Language {
+            content_dir: content_dir.as_ref(),
+            site,
+            output_dir: Path::new(config.output_dir.as_ref()).join(lang),
+        });
+    }
+    languages
+}
+
 /// The actual logic of task parallelisation.
-fn build(config: &Config) -> Result<(), Error> {
+/// Only the outputs affected by changes since the build recorded in `dependencies`
+/// (one for every language) are rendered.
+/// If `drafts` is set, also render drafts and pages that are not published at the moment.
+/// The asset manifest is made from `assets`, if given, so they are not processed again.
+fn build(
+    config: &Config,
+    dependencies: &mut Vec<Dependencies>,
+    drafts: bool,
+    assets: Option<&[assets::Asset]>,
+) -> Result<(), Error> {
     const MIN_PER_THREAD: usize = 5;
 
-    let sources: Sources<Format> = Sources::load(config.content_dir.as_ref())?;
-    let num_pages = sources.sources().len();
+    let mut languages = languages(config);
+    if config.fingerprint_assets {
+        let manifest = match assets {
+            Some(assets) => assets::manifest(config, assets)?,
+            None => assets::manifest(config, &assets::assets(config)?)?,
+        };
+        for language in languages.iter_mut() {
+            language.site.asset = manifest.clone();
+        }
+    }
+    let sources = languages
+        .iter()
+        .map(|language| Sources::load(language.content_dir))
+        .collect::<Result<Vec<Sources<Format>>, _>>()?;
+    let num_pages = sources.iter().map(|s| s.sources().len()).sum::<usize>();
     let num_threads = thread::available_parallelism()
         .map(|n| n.get())
         .unwrap_or(1);
@@ -513,25 +718,32 @@ fn build(config: &Config) -> Result<(), Error> {
     let per_thread = (num_pages / num_threads) + 1;
 
     let (templates, pages) = thread::scope(|s| {
-        let mut threads = Vec::with_capacity(num_threads);
-        for chunk in sources.sources().chunks(per_thread) {
-            threads.push(s.spawn(|| {
-                chunk
-                    .iter()
-                    .map(|src| Page::new(src, &sources))
-                    .collect::<Result<Vec<_>, _>>()
-            }));
+        let mut threads = Vec::with_capacity(num_threads + sources.len());
+        for (l, sources) in sources.iter().enumerate() {
+            for chunk in sources.sources().chunks(per_thread) {
+                let thread = s.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|src| Page::new(src, sources))
+                        .collect::<Result<Vec<_>, _>>()
+                });
+                threads.push((l, thread));
+            }
         }
         let templates = load_templates(config)?;
-        let mut pages = Vec::with_capacity(num_pages);
-        for thread in threads.drain(..) {
-            pages.append(&mut thread.join().unwind()?);
+        let mut pages: Vec<Vec<Page>> = sources
+            .iter()
+            .map(|s| Vec::with_capacity(s.sources().len()))
+            .collect();
+        for (l, thread) in threads.drain(..) {
+            pages[l].append(&mut thread.join().unwind()?);
         }
         Ok::<_, Error>((templates, pages))
     })?;
 
     // Input plugins
     // Store input pages separately, so that we can borrow from the data
+    // They belong to the main language.
     let inputs = config
         .plugins
         .input
@@ -547,93 +759,211 @@ fn build(config: &Config) -> Result<(), Error> {
         .iter()
         .map(|input| serde_json::from_slice(input))
         .collect::<Result<Vec<Vec<Page>>, _>>()?;
+    // Transform plugins, applied to each language separately
+    let mut transformed = Vec::with_capacity(languages.len());
     let mut pages = pages;
-    pages.extend(input_pages.into_iter().flat_map(|ip| ip.into_iter()));
+    pages[0].extend(input_pages.into_iter().flat_map(|ip| ip.into_iter()));
 
-    // Transform plugins
-    let mut transformed: Option<Vec<u8>> = None;
-    for cmd in config.plugins.transform.iter() {
-        let mut child = cmd
-            .make_command()
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .print_if_not_found(&cmd.path)?;
-        let mut stdin = child.stdin.take().expect("Failed to open child stdin");
-        if let Some(ref source) = transformed {
-            stdin.write_all(source)?;
-        } else {
-            serde_json::to_writer(&stdin, &pages)?;
-        }
-        drop(stdin);
-        let output = child.wait_with_output()?.output_result(&cmd.path)?;
-        transformed = Some(output);
+    for pages in pages.iter() {
+        transformed.push(transform(config, pages)?);
     }
-    let mut pages = pages;
-    if let Some(ref source) = transformed {
-        pages = serde_json::from_slice(source)?;
+    for (pages, source) in pages.iter_mut().zip(transformed.iter()) {
+        if let Some(source) = source {
+            *pages = serde_json::from_slice(source)?;
+        }
     }
 
     // Content plugins
     if !config.plugins.content.is_empty() {
-        pages.iter_mut().try_for_each(|page| {
-            let mut output: Option<String> = None;
-            for &cmd in config.plugins.default.iter().chain(page.plugins.iter()) {
-                let mut child = config.plugins.content[cmd]
-                    .make_command()
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn()
-                    .print_if_not_found(&config.plugins.content[cmd].path)?;
-                let mut stdin = child.stdin.take().expect("Failed to open child stdin");
-                if let Some(ref out) = output {
-                    stdin.write_all(out.as_ref())?;
-                } else {
-                    stdin.write_all(page.content.as_ref().as_ref())?;
+        pages
+            .iter_mut()
+            .flat_map(|pages| pages.iter_mut())
+            .try_for_each(|page| apply_content_plugins(config, page))?;
+    }
+
+    let now = DateTime::now();
+    let mut pages = pages
+        .into_iter()
+        .enumerate()
+        .map(|(l, mut pages)| {
+            let site = &languages[l].site;
+            pages
+                .iter_mut()
+                .for_each(|page| page.summarize(site.summary_paragraphs, site.summary_words));
+            if (l == 0 && !inputs.is_empty()) || transformed[l].is_some() {
+                if !drafts {
+                    pages.retain(|page| page.is_published(now));
                 }
-                drop(stdin);
-                let out = child.wait_with_output()?.output_result(cmd)?;
-                output = Some(String::from_utf8(out).map_err(|e| Error::Utf8(cmd.into(), e))?);
-            }
-            if let Some(out) = output {
-                page.content = out.into();
+                pages.sort_unstable();
+                Pages::from_external(pages)
+            } else {
+                if !drafts {
+                    pages
+                        .iter_mut()
+                        .filter(|page| !page.is_published(now))
+                        .for_each(Page::exclude);
+                }
+                Pages::from_sources(pages)
             }
-            Ok::<_, Error>(())
-        })?;
+        })
+        .collect::<Vec<_>>();
+    if languages.len() > 1 {
+        let sites: Vec<&Site> = languages.iter().map(|language| &language.site).collect();
+        Pages::link_translations(&mut pages, &sites);
+    }
+    for (pages, language) in pages.iter_mut().zip(&languages) {
+        pages
+            .resolve_links(language.site.pages_url())
+            .map_err(|(page, link)| Error::Link(page, link))?;
     }
 
-    let pages = if !inputs.is_empty() || transformed.is_some() {
-        pages.sort_unstable();
-        Pages::from_external(pages)
-    } else {
-        Pages::from_sources(pages)
-    };
+    // Image metadata and processing
+    let mut processed = Vec::new();
+    for (pages, language) in pages.iter_mut().zip(&languages) {
+        let locator = images::Locator::new(config, language.content_dir, &language.output_dir);
+        for page in pages.iter_mut() {
+            images::fill_gallery(&locator, page)?;
+        }
+        if config.exif {
+            metadata::read_exif(&locator, pages, per_thread)?;
+        }
+        if let Some(settings) = &config.images {
+            let processor = images::Processor::new(config, settings, &locator)?;
+            processed.append(&mut processor.process(pages, per_thread)?);
+        }
+        pages.iter_mut().for_each(Page::sort_pictures);
+    }
+    // The same image can be used on multiple pages
+    processed.sort_unstable();
+    processed.dedup();
+    images::prune_cache(&processed)?;
+
+    let data_files = data::read_data(config)?;
+    let data = data::parse_data(&data_files)?;
+    let mut cache = BuildCache::read(CACHE_FILE)?;
+    dependencies.resize_with(languages.len(), Dependencies::default);
+    let mut outputs = Vec::new();
+    let mut renders = Vec::with_capacity(languages.len() * (num_threads + 1));
+    let languages = languages.iter().zip(&pages).zip(dependencies).enumerate();
+    for (l, ((language, pages), dependencies)) in languages {
+        let taxonomies = Taxonomy::classify(
+            pages,
+            config.taxonomies.iter(),
+            language.site.pages_url(),
+            config.implicit_taxonomies,
+        );
+        let output_dir = language.output_dir.as_ref();
+        let context = Context(
+            pages,
+            &language.site,
+            &taxonomies,
+            &templates,
+            output_dir,
+            &data,
+        );
+        let (meta, mut rendered) = render_language(context, dependencies, &cache, per_thread)?;
+        outputs.extend(dependencies.outputs().cloned().chain(meta));
+        outputs.append(&mut copy_bundled(
+            sources[l].assets(),
+            language.content_dir,
+            output_dir,
+        )?);
+        renders.append(&mut rendered);
+    }
 
-    for page in pages.iter() {
-        page.create_directory(config.output_dir.as_ref())?;
+    outputs.append(&mut processed);
+    if let Some(compression) = &config.compression {
+        outputs.append(&mut compression.compress(&outputs, &compress::RENDERED)?);
     }
 
-    let taxonomies = Taxonomy::classify(
-        &pages,
-        config.taxonomies.iter(),
-        &config.site.url,
-        config.implicit_taxonomies,
+    let written = renders.iter().map(Rendered::written).sum::<usize>();
+    let skipped = renders.iter().map(Rendered::skipped).sum::<usize>();
+    let hashes: Vec<_> = renders
+        .into_iter()
+        .flat_map(Rendered::into_hashes)
+        .collect();
+    cache.update(hashes);
+
+    let deleted = cleanup(outputs, FILELIST, &mut cache)?;
+    cache.write(CACHE_FILE)?;
+    println!(
+        "{} files written, {} unchanged, {} deleted.",
+        written, skipped, deleted
     );
 
-    let output_dir = config.output_dir.as_ref().as_ref();
-    let context = Context(&pages, &config.site, &taxonomies, &templates, output_dir);
-    let rendered = thread::scope(|s| {
-        let mut threads = Vec::with_capacity(num_threads);
-        for chunk in pages.chunks(per_thread) {
-            threads.push(s.spawn(|| {
-                let mut rendered = Vec::with_capacity(2 * per_thread);
+    // Output plugins
+    if !config.plugins.output.is_empty() {
+        let all: Vec<&Page> = pages
+            .iter()
+            .flat_map(|pages| pages.iter())
+            .filter(|page| !page.is_excluded())
+            .collect();
+        let pagedata = serde_json::to_string(&all)?;
+        for cmd in config.plugins.output.iter() {
+            let mut child = cmd
+                .make_command()
+                .stdin(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .print_if_not_found(&cmd.path)?;
+            let mut stdin = child.stdin.take().expect("Failed to open child stdin");
+            stdin.write_all(pagedata.as_ref())?;
+            drop(stdin);
+            child
+                .wait_with_output()?
+                .output_result(&cmd.path)
+                .map(drop)?;
+        }
+    }
+
+    if config.check_links {
+        let output_dir = Path::new(config.output_dir.as_ref());
+        let broken = links::broken_links(output_dir, &config.site.url)?;
+        for (file, link) in broken.iter() {
+            eprintln!("Broken link in {}: {}", file.to_string_lossy(), link);
+        }
+        if !broken.is_empty() {
+            return Err(Error::BrokenLinks(broken.len()));
+        }
+    }
+    Ok(())
+}
+
+/// Render the pages and taxonomies of one language affected since the build recorded
+/// in `dependencies`, and its meta files, updating the `dependencies`.
+/// Return the paths of the meta files and what was rendered by every thread.
+fn render_language<'c>(
+    context: Context,
+    dependencies: &mut Dependencies,
+    cache: &'c BuildCache,
+    per_thread: usize,
+) -> Result<(Vec<PathBuf>, Vec<Rendered<'c>>), Error> {
+    let Context(pages, site, taxonomies, _, output_dir, _) = context;
+    let affected = dependencies.affected(pages, taxonomies);
+
+    for (i, page) in pages.iter().enumerate() {
+        if affected.page(i) {
+            page.create_directory(output_dir)?;
+        }
+    }
+
+    let (outputs, taxa, meta, renders) = thread::scope(|s| {
+        let mut threads = Vec::with_capacity(pages.len() / per_thread + 1);
+        for (n, chunk) in pages.chunks(per_thread).enumerate() {
+            let affected = &affected;
+            threads.push(s.spawn(move || {
+                let mut outputs = Vec::with_capacity(chunk.len());
+                let mut rendered = Rendered::new(cache, site.minify);
                 let mut buffer = Vec::with_capacity(BUFFER_SIZE);
-                for page in chunk.iter() {
-                    page.render(context, &mut rendered, &mut buffer)?;
+                for (i, page) in chunk.iter().enumerate() {
+                    if affected.page(n * per_thread + i) {
+                        page.render(context, &mut rendered, &mut buffer)?;
+                        outputs.push(Some(rendered.take()));
+                    } else {
+                        outputs.push(None);
+                    }
                 }
-                Ok::<_, Error>(rendered)
+                Ok::<_, Error>((outputs, rendered))
             }));
         }
 
@@ -642,69 +972,125 @@ fn build(config: &Config) -> Result<(), Error> {
                 .iter()
                 .map(|(_, t)| t.keys().len())
                 .sum::<usize>();
-        let mut rendered = Vec::with_capacity(tax_count + 2 * pages.len());
+        let mut taxa = Vec::with_capacity(tax_count);
+        let mut rendered = Rendered::new(cache, site.minify);
         let mut buffer = Vec::with_capacity(BUFFER_SIZE);
-        for (_, taxonomy) in taxonomies.iter() {
-            taxonomy.render(context, &mut rendered, &mut buffer)?;
-            for (n, l) in taxonomy.keys().iter() {
-                taxonomy.render_key(n, l, context, &mut rendered, &mut buffer)?;
+        for (&name, taxonomy) in taxonomies.iter() {
+            let outputs = if affected.taxon(name, None) {
+                taxonomy.render(context, &mut rendered, &mut buffer)?;
+                Some(rendered.take())
+            } else {
+                None
+            };
+            taxa.push((name, None, outputs));
+            for (&n, l) in taxonomy.keys().iter() {
+                let outputs = if affected.taxon(name, Some(n)) {
+                    taxonomy.render_key(n, l, context, &mut rendered, &mut buffer)?;
+                    Some(rendered.take())
+                } else {
+                    None
+                };
+                taxa.push((name, Some(n), outputs));
             }
         }
-        render_meta(&pages, &config.site, &taxonomies, output_dir, &mut buffer)?;
+        render_meta(context, &mut rendered, &mut buffer)?;
+        let meta = rendered.take();
 
+        let mut outputs = Vec::with_capacity(pages.len());
+        let mut renders = vec![rendered];
         for thread in threads.drain(..) {
-            let mut other = thread.join().unwind()?;
-            rendered.append(&mut other);
+            let (mut o, rendered) = thread.join().unwind()?;
+            outputs.append(&mut o);
+            renders.push(rendered);
         }
-        Ok::<_, Error>(rendered)
+        Ok::<_, Error>((outputs, taxa, meta, renders))
     })?;
 
-    cleanup(rendered, FILELIST)?;
+    dependencies.update(pages, taxonomies, outputs, taxa);
+    Ok((meta, renders))
+}
 
-    // Output plugins
-    if !config.plugins.output.is_empty() {
-        let pagedata = serde_json::to_string(&pages)?;
-        for cmd in config.plugins.output.iter() {
-            let mut child = cmd
-                .make_command()
-                .stdin(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-                .print_if_not_found(&cmd.path)?;
-            let mut stdin = child.stdin.take().expect("Failed to open child stdin");
-            stdin.write_all(pagedata.as_ref())?;
-            drop(stdin);
-            child
-                .wait_with_output()?
-                .output_result(&cmd.path)
-                .map(drop)?;
+/// Transform the pages by the transform plugins, returning their output, if there are any.
+fn transform(config: &Config, pages: &[Page]) -> Result<Option<Vec<u8>>, Error> {
+    let mut transformed: Option<Vec<u8>> = None;
+    for cmd in config.plugins.transform.iter() {
+        let mut child = cmd
+            .make_command()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .print_if_not_found(&cmd.path)?;
+        let mut stdin = child.stdin.take().expect("Failed to open child stdin");
+        if let Some(ref source) = transformed {
+            stdin.write_all(source)?;
+        } else {
+            serde_json::to_writer(&stdin, pages)?;
+        }
+        drop(stdin);
+        let output = child.wait_with_output()?.output_result(&cmd.path)?;
+        transformed = Some(output);
+    }
+    Ok(transformed)
+}
+
+/// Transform the content of the page by the default content plugins and the ones it enables.
+fn apply_content_plugins(config: &Config, page: &mut Page) -> Result<(), Error> {
+    let mut output: Option<String> = None;
+    for &cmd in config.plugins.default.iter().chain(page.plugins.iter()) {
+        let mut child = config.plugins.content[cmd]
+            .make_command()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .print_if_not_found(&config.plugins.content[cmd].path)?;
+        let mut stdin = child.stdin.take().expect("Failed to open child stdin");
+        if let Some(ref out) = output {
+            stdin.write_all(out.as_ref())?;
+        } else {
+            stdin.write_all(page.content.as_ref().as_ref())?;
         }
+        drop(stdin);
+        let out = child.wait_with_output()?.output_result(cmd)?;
+        output = Some(String::from_utf8(out).map_err(|e| Error::Utf8(cmd.into(), e))?);
+    }
+    if let Some(out) = output {
+        page.content = out.into();
     }
     Ok(())
 }
 
-fn get_command() -> Cmd {
-    let mut args = env::args().skip(1);
-    let command = match args.next().as_deref() {
+/// Parse the command and whether drafts should be rendered.
+fn get_command() -> (Cmd, bool) {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let drafts = args.iter().any(|arg| arg == "--drafts");
+    let mut args = args
+        .iter()
+        .map(String::as_str)
+        .filter(|&arg| arg != "--drafts");
+    let command = match args.next() {
         Some("init") => Cmd::Init,
         Some("new") => Cmd::New,
         Some("build") => Cmd::Build,
         Some("colocate") => Cmd::Colocate,
         Some("all") => Cmd::All,
         Some("lazy") | None => Cmd::Lazy,
+        Some("watch") => Cmd::Watch,
+        Some("serve") => Cmd::Serve,
         Some("help") => Cmd::Help,
         Some("version") => Cmd::Version,
         _ => Cmd::Invalid,
     };
     if args.next().is_some() {
-        Cmd::Invalid
+        (Cmd::Invalid, drafts)
     } else {
-        command
+        (command, drafts)
     }
 }
 
 fn main() {
-    let cmd = get_command();
+    let (cmd, drafts) = get_command();
     let config_name: Cow<str> = env::var(VAR_CONFIG)
         .map(Into::into)
         .unwrap_or_else(|_| CONFIG_FILE.into());
@@ -755,20 +1141,31 @@ fn main() {
             }
         }
         Cmd::New => new_page(&config),
-        Cmd::Build => build(&config),
-        Cmd::Colocate => colocate_assets(&config).map_err(Into::into),
-        Cmd::All => build(&config).and_then(|_| colocate_assets(&config).map_err(Into::into)),
-        Cmd::Lazy => build(&config).and_then(|_| {
+        Cmd::Build => build(&config, &mut Vec::new(), drafts, None),
+        Cmd::Colocate => {
+            assets::assets(&config).and_then(|assets| assets::colocate(&config, &assets))
+        }
+        Cmd::All => assets::assets(&config).and_then(|assets| {
+            build(&config, &mut Vec::new(), drafts, Some(&assets))?;
+            assets::colocate(&config, &assets)
+        }),
+        Cmd::Lazy => {
             if fs::read_to_string(OLD_THEME)
                 .map(|old| old != config.theme)
                 .unwrap_or(true)
+                || config.fingerprint_assets
             {
-                colocate_assets(&config)?;
-                fs::write(OLD_THEME, config.theme.as_ref()).map_err(Into::into)
+                assets::assets(&config).and_then(|assets| {
+                    build(&config, &mut Vec::new(), drafts, Some(&assets))?;
+                    assets::colocate(&config, &assets)?;
+                    fs::write(OLD_THEME, config.theme.as_ref()).map_err(Into::into)
+                })
             } else {
-                Ok(())
+                build(&config, &mut Vec::new(), drafts, None)
             }
-        }),
+        }
+        Cmd::Watch => watch::watch(&config_name, None, drafts, || {}),
+        Cmd::Serve => serve::serve(&config_name, config.output_dir.as_ref(), drafts),
         _ => {
             unreachable!()
         }
@@ -779,3 +1176,40 @@ fn main() {
 
     println!("Done in {}ms.", start.elapsed().as_micros() as f64 / 1000.0)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Format, Parser};
+
+    #[test]
+    fn markdown_headers() {
+        let page = Format::Markdown
+            .parse(b"---\ntitle: YAML\n---\nBody")
+            .unwrap();
+        assert_eq!((&*page.title, &*page.content), ("YAML", "Body"));
+        let page = Format::Markdown
+            .parse(b"{\"title\": \"JSON\"}\nBody")
+            .unwrap();
+        assert_eq!((&*page.title, &*page.content), ("JSON", "Body"));
+        let page = Format::Markdown
+            .parse(b"+++\ntitle = \"TOML\"\n+++\nBody")
+            .unwrap();
+        assert_eq!((&*page.title, &*page.content), ("TOML", "Body"));
+    }
+
+    #[test]
+    fn markdown_without_header() {
+        let page = Format::Markdown.parse(b"- item\n- another").unwrap();
+        assert_eq!(&*page.content, "- item\n- another");
+        let page = Format::Markdown.parse(b"{braces} in the text").unwrap();
+        assert_eq!(&*page.content, "{braces} in the text");
+        assert!(Format::Markdown.parse(b"{\"title\": 1}\nBody").is_err());
+    }
+
+    #[test]
+    fn markdown_empty_header() {
+        let page = Format::Markdown.parse(b"---\n---\nBody").unwrap();
+        assert_eq!(&*page.content, "Body");
+        assert_eq!(&*page.page_template, "page.html");
+    }
+}
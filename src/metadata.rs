@@ -0,0 +1,142 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::images::Locator;
+use crate::{Error, Unwind};
+use blades::{DateTime, Exif, Page, Picture};
+
+use chrono::NaiveDateTime;
+use exif::{In, Rational, Reader, Tag, Value};
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::thread;
+
+/// Read the EXIF metadata of the pictures of all the pages in parallel, filling in the date
+/// they were taken, unless it's given, and the rest of their metadata.
+/// Pictures without the metadata are left as they are.
+pub(crate) fn read_exif(
+    locator: &Locator<'_>,
+    pages: &mut [Page<'_>],
+    per_thread: usize,
+) -> Result<(), Error> {
+    thread::scope(|s| {
+        let threads: Vec<_> = pages
+            .chunks_mut(per_thread)
+            .map(|chunk| s.spawn(move || read_chunk(locator, chunk)))
+            .collect();
+        threads
+            .into_iter()
+            .try_for_each(|thread| thread.join().unwind())
+    })
+}
+
+fn read_chunk(locator: &Locator<'_>, pages: &mut [Page<'_>]) -> Result<(), Error> {
+    for page in pages.iter_mut() {
+        for i in 0..page.pictures.len() {
+            if let Some(path) = locator.find(&page.pictures[i].file, page) {
+                read_picture(&path, &mut page.pictures[i])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_picture(path: &Path, picture: &mut Picture<'_>) -> Result<(), Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let exif = match Reader::new().read_from_container(&mut reader) {
+        Ok(exif) => exif,
+        // There is no EXIF, or the format doesn't support it
+        Err(_) => return Ok(()),
+    };
+    let value = |tag| exif.get_field(tag, In::PRIMARY).map(|field| &field.value);
+    let text = |tag| match value(tag) {
+        Some(Value::Ascii(texts)) => texts
+            .first()
+            .map(|t| {
+                String::from_utf8_lossy(t)
+                    .trim_matches(['\0', ' '])
+                    .to_string()
+            })
+            .unwrap_or_default(),
+        _ => String::new(),
+    };
+    let rational = |tag| match value(tag) {
+        Some(Value::Rational(r)) => r.first().filter(|r| r.denom != 0).copied(),
+        _ => None,
+    };
+    let coordinate = |tag, ref_tag, negative| match value(tag) {
+        Some(Value::Rational(r)) if r.len() == 3 && r.iter().all(|r| r.denom != 0) => {
+            let degrees = r[0].to_f64() + r[1].to_f64() / 60.0 + r[2].to_f64() / 3600.0;
+            Some(if text(ref_tag) == negative {
+                -degrees
+            } else {
+                degrees
+            })
+        }
+        _ => None,
+    };
+
+    let (make, model) = (text(Tag::Make), text(Tag::Model));
+    let camera = if make.is_empty() || model.starts_with(&make) {
+        model
+    } else {
+        format!("{} {}", make, model)
+    };
+    let date = text(Tag::DateTimeOriginal);
+    if picture.taken.is_none() {
+        picture.taken = NaiveDateTime::parse_from_str(&date, "%Y:%m:%d %H:%M:%S")
+            .ok()
+            .map(DateTime);
+    }
+    picture.exif = Some(Exif {
+        camera: camera.into(),
+        lens: text(Tag::LensModel).into(),
+        exposure: rational(Tag::ExposureTime)
+            .map(exposure)
+            .unwrap_or_default()
+            .into(),
+        aperture: rational(Tag::FNumber)
+            .map(|f| format!("f/{}", decimal(f.to_f64())))
+            .unwrap_or_default()
+            .into(),
+        focal_length: rational(Tag::FocalLength)
+            .map(|f| format!("{} mm", decimal(f.to_f64())))
+            .unwrap_or_default()
+            .into(),
+        iso: value(Tag::PhotographicSensitivity)
+            .and_then(|v| v.get_uint(0))
+            .unwrap_or(0),
+        latitude: coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"),
+        longitude: coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"),
+        orientation: value(Tag::Orientation)
+            .and_then(|v| v.get_uint(0))
+            .unwrap_or(0) as u16,
+    });
+    Ok(())
+}
+
+/// Format the exposure time as a fraction of a second, like "1/250 s", or in seconds if longer.
+fn exposure(time: Rational) -> String {
+    if time.num > 0 && time.num < time.denom {
+        let denom = (time.denom as f64 / time.num as f64).round();
+        format!("1/{} s", denom)
+    } else {
+        format!("{} s", decimal(time.to_f64()))
+    }
+}
+
+/// Format the number with at most one decimal place.
+fn decimal(x: f64) -> String {
+    let s = format!("{:.1}", x);
+    match s.strip_suffix(".0") {
+        Some(integer) => integer.to_string(),
+        None => s,
+    }
+}
@@ -0,0 +1,194 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+
+/// Elements whose contents are kept exactly as they are.
+const VERBATIM: [&[u8]; 5] = [b"pre", b"code", b"textarea", b"script", b"style"];
+
+/// Minify the HTML, collapsing every run of whitespace outside of tags to a single space
+/// or newline and removing comments. The contents of `<pre>` (including the highlighted code
+/// blocks), `<code>`, `<textarea>`, `<script>` and `<style>` are preserved.
+pub(crate) fn html(input: &[u8], output: &mut Vec<u8>) {
+    let mut i = skip_whitespace(input, 0);
+    while i < input.len() {
+        if input[i..].starts_with(b"<!--") && !input[i..].starts_with(b"<!--[if") {
+            i = find(input, i + 4, b"-->").map_or(input.len(), |end| end + 3);
+        } else if input[i] == b'<' {
+            let end = tag_end(input, i);
+            output.extend_from_slice(&input[i..end]);
+            let name = tag_name(&input[i + 1..end]);
+            let verbatim = VERBATIM.iter().any(|v| name.eq_ignore_ascii_case(v));
+            if verbatim && !input[..end].ends_with(b"/>") {
+                let mut closing = b"</".to_vec();
+                closing.extend(name.iter().map(u8::to_ascii_lowercase));
+                let contents_end = find_ignore_case(input, end, &closing).unwrap_or(input.len());
+                output.extend_from_slice(&input[end..contents_end]);
+                i = contents_end;
+            } else {
+                i = end;
+            }
+        } else if input[i].is_ascii_whitespace() {
+            let end = skip_whitespace(input, i);
+            let newline = input[i..end].contains(&b'\n');
+            // There might be whitespace already, separated by a removed comment
+            match output.last_mut() {
+                _ if end == input.len() => {}
+                Some(last @ b' ') if newline => *last = b'\n',
+                Some(last) if last.is_ascii_whitespace() => {}
+                _ => output.push(if newline { b'\n' } else { b' ' }),
+            }
+            i = end;
+        } else {
+            output.push(input[i]);
+            i += 1;
+        }
+    }
+}
+
+/// Minify the XML, removing comments and the whitespace between tags.
+/// The text and `CDATA` sections are preserved.
+pub(crate) fn xml(input: &[u8], output: &mut Vec<u8>) {
+    let mut i = skip_whitespace(input, 0);
+    while i < input.len() {
+        if input[i..].starts_with(b"<!--") {
+            i = find(input, i + 4, b"-->").map_or(input.len(), |end| end + 3);
+            i = skip_whitespace(input, i);
+        } else if input[i..].starts_with(b"<![CDATA[") {
+            let end = find(input, i, b"]]>").map_or(input.len(), |end| end + 3);
+            output.extend_from_slice(&input[i..end]);
+            i = end;
+        } else if input[i] == b'<' {
+            let end = tag_end(input, i);
+            output.extend_from_slice(&input[i..end]);
+            i = end;
+            let text_end = skip_whitespace(input, i);
+            if input.get(text_end) == Some(&b'<') || text_end == input.len() {
+                i = text_end;
+            }
+        } else {
+            let end = find(input, i, b"<").unwrap_or(input.len());
+            output.extend_from_slice(&input[i..end]);
+            i = end;
+        }
+    }
+}
+
+fn skip_whitespace(input: &[u8], mut i: usize) -> usize {
+    while input.get(i).is_some_and(u8::is_ascii_whitespace) {
+        i += 1;
+    }
+    i
+}
+
+fn find(input: &[u8], start: usize, needle: &[u8]) -> Option<usize> {
+    input[start..]
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| start + i)
+}
+
+fn find_ignore_case(input: &[u8], start: usize, needle: &[u8]) -> Option<usize> {
+    input[start..]
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle))
+        .map(|i| start + i)
+}
+
+/// The end of the tag starting at `start`, after its closing `>`,
+/// skipping the ones in quoted attribute values.
+fn tag_end(input: &[u8], start: usize) -> usize {
+    let mut quote = None;
+    for (i, &c) in input.iter().enumerate().skip(start + 1) {
+        match (quote, c) {
+            (None, b'>') => return i + 1,
+            (None, b'"' | b'\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    input.len()
+}
+
+fn tag_name(tag: &[u8]) -> &[u8] {
+    let end = tag
+        .iter()
+        .position(|c| !c.is_ascii_alphanumeric())
+        .unwrap_or(tag.len());
+    &tag[..end]
+}
+
+#[cfg(test)]
+mod test {
+    fn html(input: &str) -> String {
+        let mut output = Vec::new();
+        super::html(input.as_bytes(), &mut output);
+        String::from_utf8(output).unwrap()
+    }
+
+    fn xml(input: &str) -> String {
+        let mut output = Vec::new();
+        super::xml(input.as_bytes(), &mut output);
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn highlighted_code_block() {
+        let block =
+            "<pre><code class=\"language-rust\"><span class=\"keyword\">fn</span> main() {\n    \
+                     println!(\"  hi  \");\n}\n</code></pre>";
+        let input = format!("<div>\n    <p>Some   text</p>\n    {}\n</div>\n", block);
+        assert_eq!(
+            html(&input),
+            format!("<div>\n<p>Some text</p>\n{}\n</div>", block)
+        );
+    }
+
+    #[test]
+    fn inline_code() {
+        assert_eq!(
+            html("<p>Use   <code>a  =  b</code>   here</p>"),
+            "<p>Use <code>a  =  b</code> here</p>"
+        );
+    }
+
+    #[test]
+    fn script_and_style() {
+        let input = "<style>\n  p  { color: red }\n</style>\n\
+                     <script>\n  let a  = \"<b>  </b>\";\n</script>";
+        assert_eq!(html(input), input);
+    }
+
+    #[test]
+    fn attribute_with_angle_bracket() {
+        assert_eq!(
+            html("<a title=\"a > b\"   href='#'>  x  </a>"),
+            "<a title=\"a > b\"   href='#'> x </a>"
+        );
+        assert_eq!(
+            html("<pre data-x=\"<code>\">  a  </pre>"),
+            "<pre data-x=\"<code>\">  a  </pre>"
+        );
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(html("<p>a <!-- note --> b</p>"), "<p>a b</p>");
+        let conditional = "<!--[if IE]><p>IE</p><![endif]-->";
+        assert_eq!(html(conditional), conditional);
+    }
+
+    #[test]
+    fn xml_cdata() {
+        let input = "<rss>\n  <!-- feed -->\n  <item>\n    \
+                     <description><![CDATA[<p>a  \n  b</p>]]></description>\n  </item>\n</rss>\n";
+        assert_eq!(
+            xml(input),
+            "<rss><item><description><![CDATA[<p>a  \n  b</p>]]></description></item></rss>"
+        );
+    }
+}
@@ -6,7 +6,7 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with Blades.  If not, see <http://www.gnu.org/licenses/>
-use crate::render::render;
+use crate::render::{render, render_feeds, Rendered};
 use crate::site::{default_true, Site};
 use crate::sources::{Parser, Source, Sources};
 use crate::taxonomies::{Classification, Taxonomies};
@@ -14,18 +14,24 @@ use crate::types::{Ancestors, Any, DateTime, HashMap};
 
 use beef::lean::Cow;
 use chrono::NaiveDate;
+use fnv::FnvHasher;
+use pulldown_cmark::{Event, Tag, TagEnd};
 use ramhorns::{
     encoding::Encoder, traits::ContentSequence, Content, Error, Ramhorns, Section, Template,
 };
 use serde::{Deserialize, Serialize};
 
 use std::cmp::{min, Ordering, Reverse};
+use std::collections::VecDeque;
+use std::fmt;
 use std::fs::create_dir_all;
+use std::hash::Hasher;
 use std::io;
 use std::num::NonZeroUsize;
 use std::ops::Range;
 use std::ops::{Deref, DerefMut};
 use std::path::{is_separator, Path, PathBuf};
+use std::sync::OnceLock;
 
 /// All the information regarding one page
 #[derive(Content, Default, Deserialize, Serialize)]
@@ -69,6 +75,9 @@ pub struct Page<'p> {
     /// An image representing the page.
     #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
     pub image: Cow<'p, str>,
+    /// Dimensions and resized versions of the image, if it was processed.
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    pub image_variants: Option<Variants<'p>>,
     /// A brief summary of the page content.
     #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
     pub summary: Cow<'p, str>,
@@ -76,48 +85,91 @@ pub struct Page<'p> {
     #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
     #[ramhorns(callback = render_content)]
     pub content: Cow<'p, str>,
+    /// The beginning of the content, up to the `<!-- more -->` marker if there is one.
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    #[ramhorns(callback = render_content)]
+    pub excerpt: Cow<'p, str>,
 
     /// Date when the page was created.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub date: Option<DateTime>,
+    /// Date when the page was last updated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated: Option<DateTime>,
 
     /// Whether to sort subpages and subsetions by their provided weight.
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     #[ramhorns(skip)]
     pub sort_by_weight: bool,
+    /// Whether to sort the pictures in the gallery by the date they were taken.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[ramhorns(skip)]
+    pub sort_pictures_by_date: bool,
     /// Is this page a section?
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub is_section: bool,
+    /// Render the feeds of the pages of this section and its subsections into its directory?
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[ramhorns(skip)]
+    pub feeds: bool,
     /// Hide the page from the list of its parent's subpages or subsections.
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub hidden: bool,
+    /// Leave the page out of the search index.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[ramhorns(skip)]
+    pub exclude_from_search: bool,
+    /// Leave the page out of the sitemap.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[ramhorns(skip)]
+    pub exclude_from_sitemap: bool,
+    /// Is this page a draft? Drafts are rendered only when previewing the site.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub draft: bool,
+    /// Date when the page gets published; it is not rendered before it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publish_date: Option<DateTime>,
+    /// Date when the page expires; it is not rendered after it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_date: Option<DateTime>,
 
     #[serde(skip, default = "default_range")]
     #[ramhorns(skip)]
-    pages: Range<usize>,
+    pub(crate) pages: Range<usize>,
     #[serde(skip, default = "default_range")]
     #[ramhorns(skip)]
-    subsections: Range<usize>,
+    pub(crate) subsections: Range<usize>,
     #[serde(skip)]
     #[ramhorns(skip)]
-    parent: usize,
+    pub(crate) parent: usize,
     #[serde(skip)]
     #[ramhorns(skip)]
-    previous: usize,
+    pub(crate) previous: usize,
     #[serde(skip)]
     #[ramhorns(skip)]
-    next: usize,
+    pub(crate) next: usize,
     #[serde(skip, default = "default_true")]
     #[ramhorns(skip)]
     nonstandard_path: bool,
-    /// Priority of this page in the sitemap
-    #[serde(skip, default = "default_priority")]
+    /// Is the page excluded from the site, because it's not published?
+    #[serde(skip)]
+    #[ramhorns(skip)]
+    pub(crate) excluded: bool,
+    /// Priority of this page in the sitemap, between 0 and 1.
+    #[serde(default = "default_priority", skip_serializing_if = "eq_def_priority")]
     pub priority: f32,
+    /// How often the page is likely to change, as stated in the sitemap (e.g. `weekly`).
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    pub changefreq: Cow<'p, str>,
 
     /// A list of pictures associated with this page.
     #[serde(default, skip_serializing_if = "is_slice_empty")]
     #[ramhorns(skip)]
     pub pictures: Box<[Picture<'p>]>,
+    /// A directory with images to fill the gallery with, in addition to the `pictures`.
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    #[ramhorns(skip)]
+    pub pictures_dir: Cow<'p, str>,
 
     /// A map of lists to classify this page with.
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
@@ -126,11 +178,49 @@ pub struct Page<'p> {
     #[serde(default, skip_serializing_if = "is_slice_empty")]
     #[ramhorns(skip)]
     pub plugins: Box<[&'p str]>,
+    /// Versions of this page in all the languages of the site, including this one.
+    /// Empty if the page is not translated.
+    #[serde(skip)]
+    pub translations: Box<[Translation]>,
+
+    /// Number of words of the content, not counting the code blocks.
+    #[serde(skip)]
+    pub word_count: usize,
+    /// Estimated time to read the content, in minutes.
+    #[serde(skip)]
+    pub reading_time: usize,
+    /// Sum of the word counts of the pages of this section.
+    #[serde(skip)]
+    pub total_word_count: usize,
+    /// Sum of the reading times of the pages of this section.
+    #[serde(skip)]
+    pub total_reading_time: usize,
 
     /// A unique number to determine whether this is the active page
     #[serde(skip)]
     #[ramhorns(skip)]
     id: usize,
+    /// Hash of the source this page was parsed from, 0 if unknown
+    #[serde(skip)]
+    #[ramhorns(skip)]
+    pub(crate) fingerprint: u64,
+    /// Path of the source relative to the content directory, without the extension.
+    /// Empty if unknown.
+    #[serde(skip)]
+    #[ramhorns(skip)]
+    source_path: &'p str,
+    /// Headings of the content arranged in a tree, found when they are first needed.
+    #[serde(skip)]
+    #[ramhorns(skip)]
+    toc: OnceLock<Vec<Heading>>,
+    /// The content rendered into HTML for the feeds, once it's needed.
+    #[serde(skip)]
+    #[ramhorns(skip)]
+    html: OnceLock<String>,
+    /// Plain text of the excerpt, used in place of the summary where it can't be HTML.
+    #[serde(skip)]
+    #[ramhorns(skip)]
+    text_summary: Box<str>,
 
     /// Any "key = value" of any type can be used here for templates.
     #[serde(flatten)]
@@ -138,13 +228,24 @@ pub struct Page<'p> {
     pub extra: HashMap<&'p str, Any<'p>>,
 }
 
+/// A link to the version of a page in some language.
+#[derive(Content)]
+pub struct Translation {
+    /// Language of this version.
+    pub lang: String,
+    /// Full link to this version.
+    pub permalink: String,
+    /// Is this the version that is being rendered?
+    pub current: bool,
+}
+
 /// A list of pages properly sorted and linked within. It dereferences to `[Page]`.
 #[derive(Serialize)]
 #[serde(transparent)]
 pub struct Pages<'p>(Box<[Page<'p>]>);
 
 /// A single picture on a page.
-#[derive(Clone, Content, Deserialize, Serialize)]
+#[derive(Clone, Content, Default, Deserialize, Serialize)]
 pub struct Picture<'p> {
     /// An alternative text displayed when the image can't be loaded or for accessibility.
     #[serde(borrow, default)]
@@ -156,13 +257,72 @@ pub struct Picture<'p> {
     #[serde(borrow)]
     pub file: Cow<'p, str>,
     /// Id string of the picture, used for the generated URL in the gallery page.
-    #[serde(borrow)]
+    /// If not given, the file name without the extension is used.
+    #[serde(borrow, default)]
     pub pid: Cow<'p, str>,
     /// Date and time of when the image was taken.
     pub taken: Option<DateTime>,
+    /// Metadata of the photo read from its EXIF, if there are some.
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    pub exif: Option<Exif<'p>>,
+    /// Dimensions and resized versions of the image, if it was processed.
+    #[serde(borrow, default, skip_serializing_if = "Variants::is_empty")]
+    #[ramhorns(flatten)]
+    pub variants: Variants<'p>,
+}
+
+/// Metadata of a photo, read from its EXIF.
+#[derive(Clone, Content, Default, Deserialize, Serialize)]
+pub struct Exif<'p> {
+    /// Make and model of the camera.
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    pub camera: Cow<'p, str>,
+    /// Model of the lens.
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    pub lens: Cow<'p, str>,
+    /// Exposure time, like "1/250 s".
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    pub exposure: Cow<'p, str>,
+    /// Aperture, like "f/2.8".
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    pub aperture: Cow<'p, str>,
+    /// Focal length, like "35 mm".
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    pub focal_length: Cow<'p, str>,
+    /// ISO sensitivity, 0 if unknown.
+    #[serde(default)]
+    pub iso: u32,
+    /// GPS latitude in degrees, positive to the north.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    /// GPS longitude in degrees, positive to the east.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// Orientation of the image, as the EXIF number from 1 to 8, 0 if unknown.
+    #[serde(default)]
+    pub orientation: u16,
+}
+
+/// Dimensions and resized versions of an image, produced by the image processing.
+#[derive(Clone, Content, Default, Deserialize, Serialize)]
+pub struct Variants<'p> {
+    /// Width of the original image in pixels.
+    #[serde(default)]
+    pub width: u32,
+    /// Height of the original image in pixels.
+    #[serde(default)]
+    pub height: u32,
+    /// Link to the thumbnail of the image.
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    pub thumbnail: Cow<'p, str>,
+    /// Links to the resized versions of the image, with their widths,
+    /// in the format of the `srcset` attribute.
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    pub srcset: Cow<'p, str>,
 }
 
 /// Whole context for rendering the site
+/// (pages, site, classification, templates, output directory, global data)
 #[derive(Clone, Copy)]
 pub struct Context<'p, 'r>(
     pub &'r Pages<'p>,
@@ -170,6 +330,7 @@ pub struct Context<'p, 'r>(
     pub &'r Classification<'p, 'r>,
     pub &'r Ramhorns,
     pub &'r Path,
+    pub &'r Any<'p>,
 );
 
 /// Page bundled with references to its subpages and subsections for rendering
@@ -198,12 +359,26 @@ struct PageContext<'p, 'r> {
     permalink: Permalink<'p, 'r>,
     site: &'r Site<'p>,
     classification: &'r Classification<'p, 'r>,
+    data: &'r Any<'p>,
     /// Always true, because this is the current page
     active: Active,
+    /// The content, rendered with the heading anchors if they are enabled.
+    content: PageContent<'r>,
+    /// Headings of the content, arranged in a tree.
+    toc: &'r [Heading],
     #[ramhorns(flatten)]
     page: &'r Page<'p>,
 }
 
+/// A heading of the page content, with the headings nested under it.
+#[derive(Clone, Content)]
+struct Heading {
+    level: u8,
+    id: String,
+    title: String,
+    children: Vec<Heading>,
+}
+
 /// (all pages, range we are interested in, id of the active page)
 /// Uses special Content implementation to render the given range of pages in context.
 #[derive(Clone)]
@@ -214,6 +389,29 @@ pub(crate) struct PageList<'p, 'r> {
     site_url: &'r str,
 }
 
+/// (all pages, pages we are interested in, in their order)
+/// Renders the selected pages in context as entries of the feeds.
+pub(crate) struct PageSelection<'p, 'r> {
+    all: &'r [Page<'p>],
+    pages: Vec<&'r Page<'p>>,
+    site_url: &'r str,
+    summary_only: bool,
+}
+
+/// A page as an entry of a feed.
+#[derive(Content)]
+struct FeedEntry<'p, 'r> {
+    feed_content: FeedContent<'p, 'r>,
+    feed_summary: FeedContent<'p, 'r>,
+    #[ramhorns(flatten)]
+    page: PageRef<'p, 'r>,
+}
+
+/// The content of the page rendered into HTML, or its summary, if only the summaries
+/// should be in the feeds. (page, summary only)
+/// `{{feed_content}}` escapes it, `{{{feed_content}}}` wraps it in a CDATA section.
+struct FeedContent<'p, 'r>(&'r Page<'p>, bool);
+
 /// Information about the current position in pagination
 #[derive(Clone, Content, Copy)]
 pub(crate) struct Pagination {
@@ -241,6 +439,7 @@ struct PictureView<'p, 'r> {
     index: PageRef<'p, 'r>,
     site: &'r Site<'p>,
     classification: &'r Classification<'p, 'r>,
+    data: &'r Any<'p>,
 }
 
 /// A list of pictures
@@ -252,6 +451,10 @@ struct Pictures<'p, 'r>(&'r [Picture<'p>], &'r Page<'p>, &'r str);
 struct Active;
 impl Content for Active {}
 
+/// Markdown content of the page, rendered with anchors at its headings if the bool is set.
+#[derive(Clone)]
+struct PageContent<'r>(&'r str, bool);
+
 /// A struct to generate the full link for the given page
 // (page, site_url)
 #[derive(Clone)]
@@ -273,7 +476,7 @@ pub(crate) trait Paginate: Content + Sized {
         by: usize,
         path: &mut PathBuf,
         tpl: &Template,
-        rendered: &mut Vec<PathBuf>,
+        rendered: &mut Rendered<'_>,
         buffer: &mut Vec<u8>,
     ) -> Result<(), io::Error> {
         let (mut first, last) = (range.start, range.end);
@@ -313,10 +516,13 @@ impl<'p> Page<'p> {
         data: &'p Sources<P>,
     ) -> Result<Self, (P::Error, Box<str>)> {
         let path = std::str::from_utf8(&data.data[source.path.clone()]).unwrap();
-        let mut page = source
-            .format
-            .parse(&data.data[source.source.clone()])
-            .map_err(|e| (e, path.into()))?;
+        let raw = &data.data[source.source.clone()];
+        let mut page = source.format.parse(raw).map_err(|e| (e, path.into()))?;
+
+        let mut hasher = FnvHasher::default();
+        hasher.write(raw);
+        page.fingerprint = hasher.finish();
+        page.source_path = path;
 
         let is_section = source.is_section;
         page.is_section = is_section;
@@ -352,6 +558,114 @@ impl<'p> Page<'p> {
         Ok(page)
     }
 
+    /// Is the page published at the given time? That is, it's not a draft and the time
+    /// is between its publish and expiry dates.
+    #[inline]
+    pub fn is_published(&self, now: DateTime) -> bool {
+        !self.draft
+            && self.publish_date.is_none_or(|date| date <= now)
+            && self.expiry_date.is_none_or(|date| now < date)
+    }
+
+    /// Exclude the page from the site. It will not be rendered, classified or listed anywhere,
+    /// and neither will be its subpages and subsections.
+    /// This has to be done before the pages are sorted and linked.
+    #[inline]
+    pub fn exclude(&mut self) {
+        self.excluded = true;
+    }
+
+    /// Is the page excluded from the site, with its subpages and subsections?
+    #[inline]
+    pub fn is_excluded(&self) -> bool {
+        self.excluded
+    }
+
+    /// Fill in the excerpt from the content, unless it's given. It's the part before
+    /// the `<!-- more -->` marker, or if there is none, the first `paragraphs` paragraphs.
+    /// If the summary is not given, the excerpt is used instead, and its plain text,
+    /// shortened to `words` words, unless it's 0, where the summary has to be a text.
+    pub fn summarize(&mut self, paragraphs: usize, words: usize) {
+        if self.excerpt.is_empty() {
+            let end = excerpt_end(&self.content, paragraphs);
+            self.excerpt = if self.content.is_borrowed() {
+                let content = self.content.clone().unwrap_borrowed();
+                Cow::borrowed(content[..end].trim_end())
+            } else {
+                Cow::owned(self.content[..end].trim_end().to_owned())
+            };
+        }
+        if self.summary.is_empty() {
+            let text = plain_text(&self.excerpt);
+            let mut summary = String::with_capacity(text.len());
+            for (i, word) in text.split_whitespace().enumerate() {
+                if words > 0 && i == words {
+                    summary.push('…');
+                    break;
+                }
+                if i > 0 {
+                    summary.push(' ');
+                }
+                summary.push_str(word);
+            }
+            self.text_summary = summary.into();
+        }
+    }
+
+    /// Sort the pictures by the date they were taken, if the page asks for it.
+    /// Pictures without the date come first.
+    #[inline]
+    pub fn sort_pictures(&mut self) {
+        if self.sort_pictures_by_date {
+            self.pictures.sort_by_key(|picture| picture.taken);
+        }
+    }
+
+    /// The content rendered into HTML, the same way as in templates, but without heading anchors.
+    pub(crate) fn content_html(&self) -> &str {
+        self.html.get_or_init(|| markdown_html(&self.content))
+    }
+
+    /// The summary, or if there is none, the plain text of the excerpt.
+    pub(crate) fn summary_text(&self) -> &str {
+        if self.summary.is_empty() {
+            &self.text_summary
+        } else {
+            &self.summary
+        }
+    }
+
+    /// The summary as HTML, or if there is none, the excerpt rendered into HTML.
+    pub(crate) fn summary_html(&self) -> String {
+        if self.summary.is_empty() {
+            markdown_html(&self.excerpt)
+        } else {
+            escape_html(&self.summary)
+        }
+    }
+
+    /// Add other data the page depends on to its fingerprint,
+    /// so it's rendered again when they change. An unknown fingerprint (0) is kept.
+    #[inline]
+    pub fn add_to_fingerprint(&mut self, data: &[u8]) {
+        if self.fingerprint == 0 {
+            return;
+        }
+        let mut hasher = FnvHasher::default();
+        hasher.write_u64(self.fingerprint);
+        hasher.write(data);
+        self.fingerprint = hasher.finish();
+    }
+
+    /// Key identifying the page: the path of its source, or its path and slug if unknown.
+    fn source_key(&self) -> String {
+        if self.source_path.is_empty() {
+            format!("{}/{}", self.path.as_ref(), self.slug)
+        } else {
+            self.source_path.to_string()
+        }
+    }
+
     /// Get a reference of the page, in context of its subpages and subsections.
     #[inline]
     pub(crate) fn by_ref<'r>(&'r self, all: &'r [Self], i: usize, url: &'r str) -> PageRef<'p, 'r> {
@@ -372,24 +686,28 @@ impl<'p> Page<'p> {
         all: &'r [Self],
         site: &'r Site<'p>,
         classification: &'r Classification<'p, 'r>,
+        data: &'r Any<'p>,
     ) -> PageContext<'p, 'r> {
         PageContext {
-            pages: PageList::new(all, self.pages.clone(), self.id, &site.url),
-            subsections: PageList::new(all, self.subsections.clone(), self.id, &site.url),
+            pages: PageList::new(all, self.pages.clone(), self.id, site.pages_url()),
+            subsections: PageList::new(all, self.subsections.clone(), self.id, site.pages_url()),
             previous: Some(self.previous)
                 .filter(|&i| i != 0)
-                .map(|i| all[i].by_ref(all, self.id, &site.url)),
+                .map(|i| all[i].by_ref(all, self.id, site.pages_url())),
             next: Some(self.next)
                 .filter(|&i| i != 0)
-                .map(|i| all[i].by_ref(all, self.id, &site.url)),
-            parent: all[self.parent].by_ref(all, self.id, &site.url),
-            pictures: Pictures(&self.pictures, self, &site.url),
-            permalink: Permalink(self, &site.url),
-            index: all[0].by_ref(all, self.id, &site.url),
+                .map(|i| all[i].by_ref(all, self.id, site.pages_url())),
+            parent: all[self.parent].by_ref(all, self.id, site.pages_url()),
+            pictures: Pictures(&self.pictures, self, site.pages_url()),
+            permalink: Permalink(self, site.pages_url()),
+            index: all[0].by_ref(all, self.id, site.pages_url()),
             pagination: None,
             classification,
             site,
+            data,
             active: Active,
+            content: PageContent(&self.content, site.heading_anchors),
+            toc: self.toc.get_or_init(|| table_of_contents(&self.content)),
             page: self,
         }
     }
@@ -398,6 +716,9 @@ impl<'p> Page<'p> {
     /// Also creates the directories specified in `alternative_paths`.
     pub fn create_directory<P: AsRef<Path>>(&self, output_dir: P) -> Result<(), io::Error> {
         let output_dir = output_dir.as_ref();
+        if self.excluded {
+            return Ok(());
+        }
 
         for path in self.alternative_paths.iter() {
             let path = output_dir.join(path);
@@ -421,10 +742,13 @@ impl<'p> Page<'p> {
     #[inline]
     pub fn render(
         &self,
-        Context(all, site, classification, templates, output_dir): Context<'p, '_>,
-        rendered: &mut Vec<PathBuf>,
+        Context(all, site, classification, templates, output_dir, data): Context<'p, '_>,
+        rendered: &mut Rendered<'_>,
         buffer: &mut Vec<u8>,
     ) -> Result<(), Error> {
+        if self.excluded {
+            return Ok(());
+        }
         let mut output = output_dir.join(self.path.as_ref());
         output.push(self.slug.as_ref());
         if self.is_section {
@@ -445,7 +769,7 @@ impl<'p> Page<'p> {
             .get(template)
             .ok_or_else(|| Error::NotFound(template.as_ref().into()))?;
 
-        let page = self.in_context(all, site, classification);
+        let page = self.in_context(all, site, classification, data);
         let by = self.paginate_by.map(NonZeroUsize::get).unwrap_or(0);
         if by > 0 && self.pages.len() > by {
             let (start, end) = (self.pages.start, self.pages.end);
@@ -466,15 +790,17 @@ impl<'p> Page<'p> {
             // Make gallery circular, with the last photo referencing the first and vice-versa
             let pictures = &self.pictures;
             let last = pictures.len() - 1;
+            let url = site.pages_url();
             for i in 0..=last {
                 let page = PictureView {
-                    current: pictures[i].by_ref(self, &site.url),
-                    previous: pictures[if i == 0 { last } else { i - 1 }].by_ref(self, &site.url),
-                    next: pictures[if i == last { 0 } else { i + 1 }].by_ref(self, &site.url),
-                    parent: self.by_ref(all, self.id, &site.url),
-                    index: all[0].by_ref(all, self.id, &site.url),
+                    current: pictures[i].by_ref(self, url),
+                    previous: pictures[if i == 0 { last } else { i - 1 }].by_ref(self, url),
+                    next: pictures[if i == last { 0 } else { i + 1 }].by_ref(self, url),
+                    parent: self.by_ref(all, self.id, url),
+                    index: all[0].by_ref(all, self.id, url),
                     site,
                     classification,
+                    data,
                 };
                 output.push(pictures[i].pid.as_ref());
                 output.set_extension("html");
@@ -490,6 +816,18 @@ impl<'p> Page<'p> {
             output.push("index.html");
             render(template, output, &page, rendered, buffer)?;
         }
+
+        // The feeds of the whole site are already in the output directory
+        if self.is_section && self.feeds && !(self.path.0.is_empty() && self.slug.is_empty()) {
+            let mut pages = Vec::new();
+            section_pages(all, self, &mut pages);
+            let path = match self.path.as_ref() {
+                "" => self.slug.to_string(),
+                path => format!("{}/{}", path, self.slug),
+            };
+            let context = Context(all, site, classification, templates, output_dir, data);
+            render_feeds(context, pages, &path, rendered, buffer)?;
+        }
         Ok(())
     }
 }
@@ -499,6 +837,7 @@ impl<'p> Pages<'p> {
     /// they will be rendered to.
     /// When applied to pages from external sources, the pages will not have any content
     /// hierarchy (subpages, subsections).
+    /// Excluded pages are moved behind the others and left out of the hierarchy.
     #[inline]
     pub fn from_sources(mut pages: Vec<Page<'p>>) -> Self {
         for i in 0..pages.len() {
@@ -506,14 +845,28 @@ impl<'p> Pages<'p> {
 
             let subpages = page.pages.clone();
             let subsects = page.subsections.clone();
+            if page.excluded {
+                for j in subpages.clone().chain(subsects.clone()) {
+                    pages[j].excluded = true;
+                }
+            }
+            let page = &pages[i];
             if page.sort_by_weight {
-                pages[subpages.clone()].sort_unstable_by_key(|p| p.weight);
-                pages[subsects.clone()].sort_unstable_by_key(|p| p.weight);
+                pages[subpages.clone()].sort_unstable_by_key(|p| (p.excluded, p.weight));
+                pages[subsects.clone()].sort_unstable_by_key(|p| (p.excluded, p.weight));
             } else {
-                pages[subpages.clone()].sort_unstable_by_key(|p| Reverse(p.date));
-                pages[subsects.clone()].sort_unstable_by_key(|p| Reverse(p.date));
+                pages[subpages.clone()].sort_unstable_by_key(|p| (p.excluded, Reverse(p.date)));
+                pages[subsects.clone()].sort_unstable_by_key(|p| (p.excluded, Reverse(p.date)));
             }
 
+            let included = |range: Range<usize>| {
+                let count = pages[range.clone()].iter().filter(|p| !p.excluded).count();
+                range.start..range.start + count
+            };
+            let (subpages, subsects) = (included(subpages), included(subsects));
+            pages[i].pages = subpages.clone();
+            pages[i].subsections = subsects.clone();
+
             for i in subpages.clone().skip(1) {
                 pages[i].previous = i - 1;
             }
@@ -530,7 +883,7 @@ impl<'p> Pages<'p> {
             // Assign a unique identifier
             pages[i].id = i;
         }
-        Pages(pages.into())
+        Self::with_statistics(pages)
     }
 
     /// Build up the internal hierarchical structure of pages loaded from the external source.
@@ -621,8 +974,85 @@ impl<'p> Pages<'p> {
             // Assign a unique identifier
             pages[i].id = i;
         }
+        Self::with_statistics(pages)
+    }
+}
+
+impl<'p> Pages<'p> {
+    /// Count the words and reading times of the pages and their totals for the sections.
+    fn with_statistics(mut pages: Vec<Page<'p>>) -> Self {
+        for page in pages.iter_mut() {
+            page.word_count = word_count(&page.content);
+            page.reading_time = page.word_count.div_ceil(WORDS_PER_MINUTE);
+        }
+        for i in 0..pages.len() {
+            let subpages = &pages[pages[i].pages.clone()];
+            let words = subpages.iter().map(|p| p.word_count).sum();
+            let time = subpages.iter().map(|p| p.reading_time).sum();
+            pages[i].total_word_count = words;
+            pages[i].total_reading_time = time;
+        }
         Pages(pages.into())
     }
+
+    /// Resolve the internal links in the content and excerpts of the pages into permalinks.
+    /// They are written as `@/path.md`, with the path of the target's source relative to the
+    /// content directory (or as `@/path/slug` for pages from external sources),
+    /// optionally followed by a `#fragment`.
+    /// If the target of some link doesn't exist, returns the page and the link.
+    pub fn resolve_links(&mut self, url: &str) -> Result<(), (Box<str>, Box<str>)> {
+        let mut permalinks = HashMap::default();
+        permalinks.extend(
+            self.iter()
+                .filter(|page| !page.excluded)
+                .map(|page| (page.source_key(), Permalink(page, url).to_string())),
+        );
+        // The root section, linked as `@/index.md` or `@/`
+        if let Some(root) = self.first() {
+            permalinks.insert(String::new(), Permalink(root, url).to_string());
+        }
+
+        for page in self.iter_mut().filter(|page| !page.excluded) {
+            let resolved = resolve_links(&page.content, &permalinks)
+                .and_then(|c| Ok((c, resolve_links(&page.excerpt, &permalinks)?)))
+                .map_err(|link| (page.source_key().into(), link.into()))?;
+            let (content, excerpt) = resolved;
+            if let Some(content) = content {
+                page.content = Cow::owned(content);
+            }
+            if let Some(excerpt) = excerpt {
+                page.excerpt = Cow::owned(excerpt);
+            }
+        }
+        Ok(())
+    }
+
+    /// Link together the versions of pages in all the languages of the site.
+    /// `languages` are the pages in every language and `sites` the corresponding sites.
+    /// Pages are versions of each other if they come from the same path in their content
+    /// directories (or have the same path and slug, if they come from external sources).
+    pub fn link_translations(languages: &mut [Pages<'p>], sites: &[&Site<'_>]) {
+        let mut versions: HashMap<String, Vec<(usize, usize)>> = HashMap::default();
+        for (l, pages) in languages.iter().enumerate() {
+            for (i, page) in pages.iter().enumerate().filter(|(_, p)| !p.excluded) {
+                versions.entry(page.source_key()).or_default().push((l, i));
+            }
+        }
+
+        for group in versions.values().filter(|group| group.len() > 1) {
+            for &(l, i) in group {
+                let translations = group
+                    .iter()
+                    .map(|&(k, j)| Translation {
+                        lang: sites[k].lang.as_ref().into(),
+                        permalink: Permalink(&languages[k][j], sites[k].pages_url()).to_string(),
+                        current: k == l,
+                    })
+                    .collect();
+                languages[l][i].translations = translations;
+            }
+        }
+    }
 }
 
 impl<'p> Ord for Page<'p> {
@@ -697,12 +1127,34 @@ impl<'p> Picture<'p> {
                 pid: self.pid.as_ref().into(),
                 file: self.file.as_ref().into(),
                 taken: self.taken,
+                exif: self.exif.as_ref().map(|exif| Exif {
+                    camera: exif.camera.as_ref().into(),
+                    lens: exif.lens.as_ref().into(),
+                    exposure: exif.exposure.as_ref().into(),
+                    aperture: exif.aperture.as_ref().into(),
+                    focal_length: exif.focal_length.as_ref().into(),
+                    ..*exif
+                }),
+                variants: Variants {
+                    width: self.variants.width,
+                    height: self.variants.height,
+                    thumbnail: self.variants.thumbnail.as_ref().into(),
+                    srcset: self.variants.srcset.as_ref().into(),
+                },
             },
             permalink: PicturePermalink(page, site_url, &self.pid),
         }
     }
 }
 
+impl<'p> Variants<'p> {
+    /// Was the image not processed?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 && self.height == 0
+    }
+}
+
 impl<'p, 'r> PageList<'p, 'r> {
     pub(crate) fn new(all: &'r [Page<'p>], range: Range<usize>, id: usize, url: &'r str) -> Self {
         Self {
@@ -714,8 +1166,44 @@ impl<'p, 'r> PageList<'p, 'r> {
     }
 }
 
+impl<'p, 'r> PageSelection<'p, 'r> {
+    pub(crate) fn new(
+        all: &'r [Page<'p>],
+        pages: Vec<&'r Page<'p>>,
+        url: &'r str,
+        summary_only: bool,
+    ) -> Self {
+        Self {
+            all,
+            pages,
+            site_url: url,
+            summary_only,
+        }
+    }
+}
+
+impl FeedContent<'_, '_> {
+    fn html(&self) -> String {
+        if self.1 {
+            self.0.summary_html()
+        } else {
+            self.0.content_html().to_string()
+        }
+    }
+}
+
+/// Collect the pages of the section and of all its subsections, leaving out the hidden ones.
+fn section_pages<'p, 'r>(all: &'r [Page<'p>], section: &Page<'p>, pages: &mut Vec<&'r Page<'p>>) {
+    let subpages = all[section.pages.clone()].iter();
+    pages.extend(subpages.filter(|page| !page.hidden && !page.excluded));
+    for subsection in all[section.subsections.clone()].iter() {
+        section_pages(all, subsection, pages);
+    }
+}
+
+/// Parse the markdown source with all the extensions Blades supports.
 #[inline]
-fn render_content<E: Encoder>(source: &str, encoder: &mut E) -> Result<(), E::Error> {
+fn parse_markdown(source: &str) -> pulldown_cmark::Parser<'_> {
     use pulldown_cmark::Options;
 
     let options = Options::ENABLE_TABLES
@@ -726,9 +1214,320 @@ fn render_content<E: Encoder>(source: &str, encoder: &mut E) -> Result<(), E::Er
         | Options::ENABLE_HEADING_ATTRIBUTES
         | Options::ENABLE_MATH
         | Options::ENABLE_GFM;
-    let parser = pulldown_cmark::Parser::new_ext(source, options);
-    let processed = cmark_syntax::SyntaxPreprocessor::new(parser);
-    encoder.write_html(processed)
+    pulldown_cmark::Parser::new_ext(source, options)
+}
+
+/// Find the destinations of all the internal links (starting with `@/`) in the markdown source,
+/// both in the inline links and in the link reference definitions.
+fn internal_links(source: &str) -> Vec<Range<usize>> {
+    use pulldown_cmark::{Event, Tag};
+
+    let find = |span: Range<usize>, dest: &str| {
+        source[span.clone()]
+            .rfind(dest)
+            .map(|i| span.start + i..span.start + i + dest.len())
+    };
+    let mut parser = parse_markdown(source).into_offset_iter();
+    let mut links: Vec<_> = parser
+        .by_ref()
+        .filter_map(|(event, span)| match event {
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. })
+                if dest_url.starts_with("@/") =>
+            {
+                find(span, &dest_url)
+            }
+            _ => None,
+        })
+        .collect();
+    let definitions = parser.reference_definitions().iter();
+    links.extend(definitions.filter_map(|(_, def)| {
+        Some(&def.dest)
+            .filter(|dest| dest.starts_with("@/"))
+            .and_then(|dest| find(def.span.clone(), dest))
+    }));
+    links.sort_unstable_by_key(|link| link.start);
+    links.dedup();
+    links
+}
+
+/// Replace the internal links in the markdown source by the permalinks of their targets.
+/// Returns `None` if there are none, or the link whose target doesn't exist.
+fn resolve_links(
+    source: &str,
+    permalinks: &HashMap<String, String>,
+) -> Result<Option<String>, String> {
+    let links = internal_links(source);
+    if links.is_empty() {
+        return Ok(None);
+    }
+    let mut resolved = String::with_capacity(source.len());
+    let mut last = 0;
+    for link in links {
+        let dest = &source[link.clone()];
+        let (target, fragment) = dest[2..].split_at(dest[2..].find('#').unwrap_or(dest.len() - 2));
+        let target = target.trim_end_matches('/');
+        let target = match target.rfind('.') {
+            Some(i) if !target[i..].contains('/') => &target[..i],
+            _ => target,
+        };
+        let target = match target.rsplit_once('/') {
+            Some((section, "index")) => section,
+            None if target == "index" => "",
+            _ => target,
+        };
+        let permalink = permalinks.get(target).ok_or_else(|| dest.to_string())?;
+        resolved.push_str(&source[last..link.start]);
+        resolved.push_str(permalink);
+        resolved.push_str(fragment);
+        last = link.end;
+    }
+    resolved.push_str(&source[last..]);
+    Ok(Some(resolved))
+}
+
+/// Marker separating the excerpt of the content from the rest.
+static MORE_MARKER: &str = "<!-- more -->";
+
+/// Find where the excerpt of the markdown source ends: at the `<!-- more -->` marker,
+/// or if there is none, after the given number of top-level paragraphs.
+fn excerpt_end(source: &str, paragraphs: usize) -> usize {
+    use pulldown_cmark::{Event, TagEnd};
+
+    let mut end = if paragraphs == 0 { 0 } else { source.len() };
+    let (mut depth, mut count) = (0, 0);
+    for (event, range) in parse_markdown(source).into_offset_iter() {
+        match event {
+            Event::Html(html) | Event::InlineHtml(html) if html.trim() == MORE_MARKER => {
+                return range.start;
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(tag) => {
+                depth -= 1;
+                if depth == 0 && tag == TagEnd::Paragraph {
+                    count += 1;
+                    if count == paragraphs {
+                        end = range.end;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    end
+}
+
+/// Events of the parsed markdown, in which every heading without an explicit `{#id}` gets an id
+/// made from its text, unique within the page. If `anchors` is set, every heading ends with
+/// a link to itself. The (level, id, title) of the headings that were passed are kept.
+struct HeadingIds<'a, I> {
+    events: I,
+    anchors: bool,
+    /// The rest of the current heading, already read to find out its title.
+    pending: VecDeque<Event<'a>>,
+    headings: Vec<(u8, String, String)>,
+}
+
+impl<'a, I: Iterator<Item = Event<'a>>> HeadingIds<'a, I> {
+    fn new(events: I, anchors: bool) -> Self {
+        Self {
+            events,
+            anchors,
+            pending: VecDeque::new(),
+            headings: Vec::new(),
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = Event<'a>>> Iterator for HeadingIds<'a, I> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        let (level, id, classes, attrs) = match self.events.next()? {
+            Event::Start(Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            }) => (level, id, classes, attrs),
+            event => return Some(event),
+        };
+
+        let mut title = String::new();
+        for event in self.events.by_ref() {
+            if let Event::End(TagEnd::Heading(_)) = event {
+                self.pending.push_back(event);
+                break;
+            }
+            if let Event::Text(t) | Event::Code(t) | Event::InlineMath(t) = &event {
+                title.push_str(t);
+            }
+            self.pending.push_back(event);
+        }
+        let id = match id {
+            Some(id) => id.to_string(),
+            None => {
+                let slug = slugify(&title);
+                let mut id = slug.clone();
+                let mut n = 0;
+                while self.headings.iter().any(|(_, used, _)| *used == id) {
+                    n += 1;
+                    id = format!("{}-{}", slug, n);
+                }
+                id
+            }
+        };
+        if self.anchors {
+            let anchor = format!(
+                "<a class=\"anchor\" href=\"#{}\" aria-hidden=\"true\">#</a>",
+                id.replace('"', "&quot;")
+            );
+            let end = self.pending.len().saturating_sub(1);
+            self.pending.insert(end, Event::InlineHtml(anchor.into()));
+        }
+        self.headings.push((level as u8, id.clone(), title));
+        Some(Event::Start(Tag::Heading {
+            level,
+            id: Some(id.into()),
+            classes,
+            attrs,
+        }))
+    }
+}
+
+/// Make an id from the heading text: lowercase alphanumeric words joined by dashes.
+fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(slug.trim_end_matches('-').len());
+    if slug.is_empty() {
+        slug.push_str("section");
+    }
+    slug
+}
+
+/// Arrange the headings of the markdown source into a tree, with every heading containing
+/// the following ones of a deeper level.
+fn table_of_contents(source: &str) -> Vec<Heading> {
+    let mut events = HeadingIds::new(parse_markdown(source), false);
+    events.by_ref().for_each(drop);
+    let mut toc: Vec<Heading> = Vec::new();
+    for (level, id, title) in events.headings {
+        let mut list = &mut toc;
+        while list.last().is_some_and(|h| h.level < level) {
+            list = &mut list.last_mut().unwrap().children;
+        }
+        list.push(Heading {
+            level,
+            id,
+            title,
+            children: Vec::new(),
+        });
+    }
+    toc
+}
+
+#[inline]
+fn render_content<E: Encoder>(source: &str, encoder: &mut E) -> Result<(), E::Error> {
+    let events = HeadingIds::new(parse_markdown(source), false);
+    encoder.write_html(cmark_syntax::SyntaxPreprocessor::new(events))
+}
+
+/// Render the markdown source into HTML, the same way as the content in templates,
+/// but without heading anchors.
+fn markdown_html(source: &str) -> String {
+    let mut html = String::with_capacity(source.len() * 3 / 2);
+    let events = HeadingIds::new(parse_markdown(source), false);
+    pulldown_cmark::html::push_html(&mut html, cmark_syntax::SyntaxPreprocessor::new(events));
+    html
+}
+
+/// Escape the text to be included in HTML.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reading speed used to estimate the reading time.
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Count the words of the markdown source, leaving out the markup and the code blocks.
+fn word_count(source: &str) -> usize {
+    use pulldown_cmark::{Event, Tag, TagEnd};
+
+    let (mut count, mut in_code, mut in_word) = (0, false, false);
+    for event in parse_markdown(source) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(TagEnd::CodeBlock) => in_code = false,
+            Event::Text(t) | Event::Code(t) if !in_code => {
+                // Words can be split into several events, e.g. by smart punctuation.
+                for c in t.chars() {
+                    if c.is_alphanumeric() {
+                        count += !in_word as usize;
+                        in_word = true;
+                    } else if c.is_whitespace() {
+                        in_word = false;
+                    }
+                }
+            }
+            Event::SoftBreak
+            | Event::HardBreak
+            | Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::Item
+                | TagEnd::TableCell
+                | TagEnd::BlockQuote,
+            ) => in_word = false,
+            _ => {}
+        }
+    }
+    count
+}
+
+/// Render the markdown source as a plain text, without any markup.
+pub(crate) fn plain_text(source: &str) -> String {
+    use pulldown_cmark::{Event, TagEnd};
+
+    let mut text = String::with_capacity(source.len());
+    for event in parse_markdown(source) {
+        match event {
+            Event::Text(t) | Event::Code(t) | Event::InlineMath(t) | Event::DisplayMath(t) => {
+                text.push_str(&t)
+            }
+            Event::SoftBreak
+            | Event::HardBreak
+            | Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::Item
+                | TagEnd::TableCell
+                | TagEnd::CodeBlock
+                | TagEnd::BlockQuote,
+            ) if !text.ends_with(' ') => text.push(' '),
+            _ => {}
+        }
+    }
+    text.truncate(text.trim_end().len());
+    text
 }
 
 impl<'p, 'r> Content for PageList<'p, 'r> {
@@ -744,7 +1543,7 @@ impl<'p, 'r> Content for PageList<'p, 'r> {
         E: Encoder,
     {
         let range = self.range.clone();
-        for page in self.all[range].iter().filter(|p| !p.hidden) {
+        for page in self.all[range].iter().filter(|p| !p.hidden && !p.excluded) {
             page.by_ref(self.all, self.active, self.site_url)
                 .render_section(section, encoder)?;
         }
@@ -753,6 +1552,74 @@ impl<'p, 'r> Content for PageList<'p, 'r> {
     }
 }
 
+impl<'p, 'r> Content for PageSelection<'p, 'r> {
+    #[inline]
+    fn is_truthy(&self) -> bool {
+        !self.pages.is_empty()
+    }
+
+    #[inline]
+    fn render_section<C, E>(&self, section: Section<C>, encoder: &mut E) -> Result<(), E::Error>
+    where
+        C: ContentSequence,
+        E: Encoder,
+    {
+        for page in self.pages.iter() {
+            let entry = FeedEntry {
+                feed_content: FeedContent(page, self.summary_only),
+                feed_summary: FeedContent(page, true),
+                page: page.by_ref(self.all, usize::MAX, self.site_url),
+            };
+            entry.render_section(section, encoder)?;
+        }
+        Ok(())
+    }
+}
+
+impl Content for FeedContent<'_, '_> {
+    #[inline]
+    fn is_truthy(&self) -> bool {
+        let page = self.0;
+        if self.1 {
+            !page.summary.is_empty() || !page.excerpt.is_empty()
+        } else {
+            !page.content.is_empty()
+        }
+    }
+
+    #[inline]
+    fn render_escaped<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        encoder.write_escaped(&self.html())
+    }
+
+    #[inline]
+    fn render_unescaped<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        // The end of the CDATA section can't be a part of it
+        let html = self.html().replace("]]>", "]]]]><![CDATA[>");
+        encoder.write_unescaped("<![CDATA[")?;
+        encoder.write_unescaped(&html)?;
+        encoder.write_unescaped("]]>")
+    }
+}
+
+impl<'r> Content for PageContent<'r> {
+    #[inline]
+    fn is_truthy(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    #[inline]
+    fn render_escaped<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        let events = HeadingIds::new(parse_markdown(self.0), self.1);
+        encoder.write_html(cmark_syntax::SyntaxPreprocessor::new(events))
+    }
+
+    #[inline]
+    fn render_unescaped<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        self.render_escaped(encoder)
+    }
+}
+
 impl<'p, 'r> Content for Permalink<'p, 'r> {
     #[inline]
     fn is_truthy(&self) -> bool {
@@ -790,6 +1657,20 @@ impl<'p, 'r> Content for Permalink<'p, 'r> {
     }
 }
 
+impl fmt::Display for Permalink<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.1)?;
+        let path = self.0.path.as_ref();
+        if !path.is_empty() {
+            write!(f, "/{}", path)?;
+        }
+        if !self.0.slug.is_empty() {
+            write!(f, "/{}", self.0.slug)?;
+        }
+        f.write_str(if self.0.is_section { "/" } else { ".html" })
+    }
+}
+
 impl<'p, 'r> Content for Pictures<'p, 'r> {
     #[inline]
     fn is_truthy(&self) -> bool {
@@ -884,6 +1765,11 @@ const fn def_section() -> Cow<'static, str> {
     Cow::const_str("section.html")
 }
 
+#[inline]
+fn eq_def_priority(p: &f32) -> bool {
+    *p == default_priority()
+}
+
 #[inline]
 fn eq_def_gall(c: &str) -> bool {
     c == "gallery.html"
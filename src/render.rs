@@ -6,84 +6,417 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with Blades.  If not, see <http://www.gnu.org/licenses/>
-use crate::page::{Page, PageList};
+use crate::feed::json_feed;
+use crate::minify;
+use crate::page::{Context, Page, PageSelection};
+use crate::search::search_index;
 use crate::site::Site;
-use crate::taxonomies::{Classification, TaxonList};
-use crate::types::DateTime;
+use crate::sitemap::{self, Url};
+use crate::taxonomies::TaxonList;
+use crate::types::{Any, DateTime, HashMap};
 
-use std::fs;
-use std::io;
+use fnv::FnvHasher;
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::mem;
 use std::path::{Path, PathBuf};
 
-use ramhorns::{Content, Template};
+use ramhorns::{Content, Ramhorns, Template};
+
+/// Hashes of the contents of the files rendered in the previous builds,
+/// used to avoid writing the files that did not change.
+#[derive(Default)]
+pub struct BuildCache(HashMap<PathBuf, u64>);
+
+/// Files rendered during the build, together with the hashes of their contents.
+pub struct Rendered<'c> {
+    cache: &'c BuildCache,
+    /// Paths rendered since they were last taken.
+    paths: Vec<PathBuf>,
+    hashes: Vec<(PathBuf, u64)>,
+    written: usize,
+    /// Minify the HTML and XML files?
+    minify: bool,
+    /// Buffer for the minified contents.
+    minified: Vec<u8>,
+}
+
+impl BuildCache {
+    /// Read the cache from the given file. If it doesn't exist, the cache is empty.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let mut cache = BuildCache::default();
+        let f = match File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(cache),
+            Err(e) => return Err(e),
+        };
+        for line in BufReader::new(f).lines() {
+            let line = line?;
+            // A malformed line only means that the file will be written again.
+            if let Some((hash, path)) = line.split_once(' ') {
+                if let Ok(hash) = u64::from_str_radix(hash, 16) {
+                    cache.0.insert(path.into(), hash);
+                }
+            }
+        }
+        Ok(cache)
+    }
+
+    /// Write the cache into the given file.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let mut f = BufWriter::new(File::create(path)?);
+        for (path, hash) in self.0.iter() {
+            writeln!(&mut f, "{:016x} {}", hash, path.to_string_lossy())?;
+        }
+        f.flush()
+    }
+
+    /// Record the hashes of the files rendered in the current build.
+    pub fn update<I: IntoIterator<Item = (PathBuf, u64)>>(&mut self, hashes: I) {
+        self.0.extend(hashes);
+    }
+
+    /// Forget all the files that are not matched by the predicate.
+    pub fn retain<F: FnMut(&Path) -> bool>(&mut self, mut f: F) {
+        self.0.retain(|path, _| f(path));
+    }
+}
+
+impl<'c> Rendered<'c> {
+    /// Start rendering, skipping files whose contents match the ones in the `cache`.
+    /// If `minify` is set, the HTML and XML files are minified before they are written.
+    #[inline]
+    pub fn new(cache: &'c BuildCache, minify: bool) -> Self {
+        Self {
+            cache,
+            paths: Vec::new(),
+            hashes: Vec::new(),
+            written: 0,
+            minify,
+            minified: Vec::new(),
+        }
+    }
+
+    /// Take the paths rendered since the last time they were taken.
+    #[inline]
+    pub fn take(&mut self) -> Vec<PathBuf> {
+        mem::take(&mut self.paths)
+    }
+
+    /// The number of files that were written.
+    #[inline]
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// The number of files that were skipped, because their contents didn't change.
+    #[inline]
+    pub fn skipped(&self) -> usize {
+        self.hashes.len() - self.written
+    }
+
+    /// Paths of all the rendered files with the hashes of their contents.
+    #[inline]
+    pub fn into_hashes(self) -> Vec<(PathBuf, u64)> {
+        self.hashes
+    }
+
+    /// Write the contents of `buffer` to the file, unless it already contains them, and clear it.
+    fn write(&mut self, path: PathBuf, buffer: &mut Vec<u8>) -> Result<(), io::Error> {
+        if self.minify {
+            let minifier = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("html" | "htm") => Some(minify::html as fn(&[u8], &mut Vec<u8>)),
+                Some("xml") => Some(minify::xml as fn(&[u8], &mut Vec<u8>)),
+                _ => None,
+            };
+            if let Some(minifier) = minifier {
+                minifier(buffer, &mut self.minified);
+                mem::swap(buffer, &mut self.minified);
+                self.minified.clear();
+            }
+        }
+        let mut hasher = FnvHasher::default();
+        hasher.write(buffer);
+        let hash = hasher.finish();
+        if self.cache.0.get(&path) != Some(&hash) || !path.exists() {
+            fs::write(&path, &buffer)?;
+            self.written += 1;
+        }
+        buffer.clear();
+        self.hashes.push((path.clone(), hash));
+        self.paths.push(path);
+        Ok(())
+    }
+}
 
 #[inline]
 pub(crate) fn render<P, C>(
     template: &Template,
     path: P,
     content: &C,
-    rendered: &mut Vec<PathBuf>,
+    rendered: &mut Rendered<'_>,
     buffer: &mut Vec<u8>,
 ) -> Result<(), io::Error>
 where
     C: Content,
     P: Into<PathBuf>,
 {
-    let path = path.into();
     // Can't fail
     let _ = template.render_to_writer(buffer, content);
-    fs::write(&path, &buffer)?;
-    buffer.clear();
-    rendered.push(path);
-    Ok(())
+    rendered.write(path.into(), buffer)
 }
 
 #[derive(Content)]
 struct Meta<'p, 'r>(
     #[ramhorns(rename = "date")] DateTime,
-    #[ramhorns(rename = "pages")] PageList<'p, 'r>,
+    #[ramhorns(rename = "pages")] PageSelection<'p, 'r>,
     #[ramhorns(rename = "taxons")] TaxonList<'p, 'r>,
     #[ramhorns(rename = "site")] &'r Site<'p>,
+    #[ramhorns(rename = "data")] &'r Any<'p>,
 );
 
+/// The sitemap, or one part of it, if it's split.
+#[derive(Content)]
+struct Sitemap<'p, 'r, 'm> {
+    urls: &'m [Url<'r>],
+    #[ramhorns(flatten)]
+    meta: &'m Meta<'p, 'r>,
+}
+
+/// The sitemap index, listing the parts of a sitemap too long for one file.
+#[derive(Content)]
+struct SitemapIndex<'p, 'r, 'm> {
+    sitemaps: &'m [Url<'r>],
+    #[ramhorns(flatten)]
+    meta: &'m Meta<'p, 'r>,
+}
+
+/// Built-in templates of the meta files, used unless the site or its theme has its own.
+const META_TEMPLATES: [(&str, &str); 4] = [
+    ("sitemap.xml", include_str!("templates/sitemap.xml")),
+    (
+        "sitemap_index.xml",
+        include_str!("templates/sitemap_index.xml"),
+    ),
+    ("rss.xml", include_str!("templates/rss.xml")),
+    ("atom.xml", include_str!("templates/atom.xml")),
+];
+
+/// Add the built-in templates of the sitemap, sitemap index, RSS and Atom feeds to the `templates`,
+/// unless they contain their own already.
+pub fn add_meta_templates(templates: &mut Ramhorns) -> Result<(), ramhorns::Error> {
+    for (name, source) in META_TEMPLATES {
+        if templates.get(name).is_none() {
+            templates.insert(source, name)?;
+        }
+    }
+    Ok(())
+}
+
 impl<'p> Meta<'p, '_> {
+    /// Render the template of the given name into the file of the same name in the directory.
     #[inline]
     fn render(
         &self,
         name: &str,
-        template: &str,
-        path: &Path,
+        templates: &Ramhorns,
+        dir: &Path,
+        rendered: &mut Rendered<'_>,
         buffer: &mut Vec<u8>,
     ) -> Result<(), ramhorns::Error> {
-        let _ = Template::new(template)?.render_to_writer(buffer, self);
-        fs::write(path.join(name), &buffer)?;
-        buffer.clear();
-        Ok(())
+        render_meta_file(self, name, templates, dir.join(name), rendered, buffer)
     }
 }
 
-/// Render sitemap, Atom and RSS feeds if enabled in the config.
-pub fn render_meta<'p>(
-    pages: &[Page<'p>],
-    site: &Site<'p>,
-    taxons: &Classification<'p, '_>,
+/// Render the content with the template of the given name into the file at `path`.
+/// The built-in templates are used if they were not added to the `templates`.
+fn render_meta_file<C: Content>(
+    content: &C,
+    name: &str,
+    templates: &Ramhorns,
+    path: PathBuf,
+    rendered: &mut Rendered<'_>,
+    buffer: &mut Vec<u8>,
+) -> Result<(), ramhorns::Error> {
+    let builtin;
+    let template = match templates.get(name) {
+        Some(template) => template,
+        None => {
+            let source = META_TEMPLATES
+                .iter()
+                .find(|(builtin, _)| *builtin == name)
+                .map(|(_, source)| *source)
+                .ok_or_else(|| ramhorns::Error::NotFound(name.into()))?;
+            builtin = Template::new(source)?;
+            &builtin
+        }
+    };
+    render(template, path, content, rendered, buffer)?;
+    Ok(())
+}
+
+/// Render the sitemap of the pages and taxonomies. If it has more URLs than the protocol allows,
+/// it's split into `sitemap1.xml`, `sitemap2.xml` and so on, listed in the index in `sitemap.xml`.
+fn render_sitemap(
+    meta: &Meta<'_, '_>,
+    urls: &[Url<'_>],
+    templates: &Ramhorns,
     output_dir: &Path,
+    rendered: &mut Rendered<'_>,
+    buffer: &mut Vec<u8>,
+) -> Result<(), ramhorns::Error> {
+    if urls.len() <= sitemap::MAX_URLS {
+        let sitemap = Sitemap { urls, meta };
+        let path = output_dir.join("sitemap.xml");
+        return render_meta_file(&sitemap, "sitemap.xml", templates, path, rendered, buffer);
+    }
+
+    let url = meta.3.pages_url().trim_end_matches('/');
+    let mut sitemaps = Vec::new();
+    for (i, urls) in urls.chunks(sitemap::MAX_URLS).enumerate() {
+        let name = format!("sitemap{}.xml", i + 1);
+        sitemaps.push(Url::index(format!("{}/{}", url, name), urls));
+        let sitemap = Sitemap { urls, meta };
+        let path = output_dir.join(name);
+        render_meta_file(&sitemap, "sitemap.xml", templates, path, rendered, buffer)?;
+    }
+    let index = SitemapIndex {
+        sitemaps: &sitemaps,
+        meta,
+    };
+    let path = output_dir.join("sitemap.xml");
+    render_meta_file(
+        &index,
+        "sitemap_index.xml",
+        templates,
+        path,
+        rendered,
+        buffer,
+    )
+}
+
+/// Render sitemap, Atom, RSS and JSON feeds and the search index if enabled in the config,
+/// and the other meta files listed in it.
+pub fn render_meta(
+    Context(all, site, taxons, templates, output_dir, data): Context<'_, '_>,
+    rendered: &mut Rendered<'_>,
     buffer: &mut Vec<u8>,
 ) -> Result<(), ramhorns::Error> {
-    let pages = PageList::new(pages, 0..pages.len(), 0, &site.url);
-    let meta = Meta(DateTime::now(), pages, TaxonList(taxons), site);
+    let pages: Vec<_> = all.iter().filter(|p| !p.hidden && !p.excluded).collect();
+    if site.sitemap || !site.meta_outputs.is_empty() {
+        let selection = PageSelection::new(all, pages.clone(), site.pages_url(), false);
+        let meta = Meta(DateTime::now(), selection, TaxonList(taxons), site, data);
+        if site.sitemap {
+            let urls = sitemap::urls(&pages, taxons, site);
+            render_sitemap(&meta, &urls, templates, output_dir, rendered, buffer)?;
+        }
+        for name in site.meta_outputs.iter() {
+            if let Some(parent) = Path::new(name.as_ref()).parent() {
+                fs::create_dir_all(output_dir.join(parent))?;
+            }
+            meta.render(name, templates, output_dir, rendered, buffer)?;
+        }
+    }
+    let context = Context(all, site, taxons, templates, output_dir, data);
+    render_feeds(context, pages, "", rendered, buffer)?;
+    if site.search_index {
+        search_index(all, site.pages_url(), buffer)?;
+        rendered.write(output_dir.join("search_index.json"), buffer)?;
+    }
+    Ok(())
+}
 
-    if site.sitemap {
-        let sitemap = include_str!("templates/sitemap.xml");
-        meta.render("sitemap.xml", sitemap, output_dir, buffer)?;
+/// Render the feeds enabled in the config of the given pages into the subdirectory `path`
+/// of the output directory, such as the one of a section or a taxonomy key.
+/// The pages are selected, ordered and limited according to the feed settings of the site.
+pub(crate) fn render_feeds<'p, 'r>(
+    Context(all, site, taxons, templates, output_dir, data): Context<'p, 'r>,
+    mut pages: Vec<&'r Page<'p>>,
+    path: &str,
+    rendered: &mut Rendered<'_>,
+    buffer: &mut Vec<u8>,
+) -> Result<(), ramhorns::Error> {
+    let settings = &site.feed;
+    if !settings.sections {
+        pages.retain(|page| !page.is_section);
+    }
+    if settings.sort_by_date {
+        pages.sort_by_key(|page| Reverse(page.date));
+    }
+    if settings.limit > 0 {
+        pages.truncate(settings.limit);
     }
+
+    let dir = output_dir.join(path);
+    if site.json_feed {
+        json_feed(&pages, site, path, buffer)?;
+        rendered.write(dir.join("feed.json"), buffer)?;
+    }
+    let selection = PageSelection::new(all, pages, site.pages_url(), settings.summary_only);
+    let meta = Meta(DateTime::now(), selection, TaxonList(taxons), site, data);
     if site.rss {
-        let rss = include_str!("templates/rss.xml");
-        meta.render("rss.xml", rss, output_dir, buffer)?;
+        meta.render("rss.xml", templates, &dir, rendered, buffer)?;
     }
     if site.atom {
-        let atom = include_str!("templates/atom.xml");
-        meta.render("atom.xml", atom, output_dir, buffer)?;
+        meta.render("atom.xml", templates, &dir, rendered, buffer)?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Render the sitemap of the given number of URLs and return the names of the written files.
+    fn sitemap_files(count: usize) -> Vec<String> {
+        let dir =
+            std::env::temp_dir().join(format!("blades-sitemap-{}-{}", std::process::id(), count));
+        fs::create_dir_all(&dir).unwrap();
+        let templates = Ramhorns::from_folder(&dir).unwrap();
+        let site = Site::default();
+        let classification = HashMap::default();
+        let data = Any::Bool(false);
+        let selection = PageSelection::new(&[], Vec::new(), "", false);
+        let meta = Meta(
+            DateTime::now(),
+            selection,
+            TaxonList(&classification),
+            &site,
+            &data,
+        );
+        let urls: Vec<_> = (0..count)
+            .map(|i| Url::index(format!("/{}.html", i), &[]))
+            .collect();
+
+        let cache = BuildCache::default();
+        let mut rendered = Rendered::new(&cache, false);
+        render_sitemap(
+            &meta,
+            &urls,
+            &templates,
+            &dir,
+            &mut rendered,
+            &mut Vec::new(),
+        )
+        .unwrap();
+        let mut files: Vec<_> = rendered
+            .take()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        files.sort_unstable();
+        fs::remove_dir_all(&dir).unwrap();
+        files
+    }
+
+    #[test]
+    fn sitemap_split() {
+        assert_eq!(sitemap_files(sitemap::MAX_URLS), ["sitemap.xml"]);
+        assert_eq!(
+            sitemap_files(sitemap::MAX_URLS + 1),
+            ["sitemap.xml", "sitemap1.xml", "sitemap2.xml"]
+        );
+    }
+}
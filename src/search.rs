@@ -0,0 +1,116 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::page::{plain_text, Page, Permalink};
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// Write the search index of the pages into the buffer as JSON.
+/// It consists of a list of documents (title, permalink, summary and taxonomies of every page)
+/// and an inverted index, mapping every word to a list of pairs
+/// (position of the document in the list, number of occurrences of the word in it).
+pub(crate) fn search_index(pages: &[Page<'_>], url: &str, buffer: &mut Vec<u8>) -> io::Result<()> {
+    let mut index: BTreeMap<String, Vec<(usize, usize)>> = BTreeMap::new();
+    buffer.extend_from_slice(b"{\"documents\":[");
+    let searchable = pages
+        .iter()
+        .filter(|page| !page.excluded && !page.exclude_from_search);
+    for (doc, page) in searchable.enumerate() {
+        if doc > 0 {
+            buffer.push(b',');
+        }
+        buffer.extend_from_slice(b"{\"title\":");
+        write_json_str(buffer, &page.title)?;
+        buffer.extend_from_slice(b",\"url\":");
+        write_json_str(buffer, &Permalink(page, url).to_string())?;
+        buffer.extend_from_slice(b",\"summary\":");
+        write_json_str(buffer, page.summary_text())?;
+        buffer.extend_from_slice(b",\"taxonomies\":{");
+        let mut taxonomies: Vec<_> = page.taxonomies.iter().collect();
+        taxonomies.sort_unstable_by_key(|(name, _)| *name);
+        for (i, (name, family)) in taxonomies.into_iter().enumerate() {
+            if i > 0 {
+                buffer.push(b',');
+            }
+            write_json_str(buffer, name)?;
+            buffer.extend_from_slice(b":[");
+            for (j, species) in family.iter().enumerate() {
+                if j > 0 {
+                    buffer.push(b',');
+                }
+                write_json_str(buffer, species)?;
+            }
+            buffer.push(b']');
+        }
+        buffer.extend_from_slice(b"}}");
+
+        let content = plain_text(&page.content);
+        let species = page
+            .taxonomies
+            .values()
+            .flat_map(|f| f.iter().map(|s| &**s));
+        let texts = [&*page.title, page.summary_text(), &content]
+            .into_iter()
+            .chain(species);
+        for word in texts.flat_map(|t| t.split(|c: char| !c.is_alphanumeric())) {
+            if word.is_empty() {
+                continue;
+            }
+            let postings = index.entry(word.to_lowercase()).or_default();
+            match postings.last_mut() {
+                Some((d, count)) if *d == doc => *count += 1,
+                _ => postings.push((doc, 1)),
+            }
+        }
+    }
+
+    buffer.extend_from_slice(b"],\"index\":{");
+    for (i, (word, postings)) in index.iter().enumerate() {
+        if i > 0 {
+            buffer.push(b',');
+        }
+        write_json_str(buffer, word)?;
+        buffer.extend_from_slice(b":[");
+        for (j, (doc, count)) in postings.iter().enumerate() {
+            if j > 0 {
+                buffer.push(b',');
+            }
+            write!(buffer, "[{},{}]", doc, count)?;
+        }
+        buffer.push(b']');
+    }
+    buffer.extend_from_slice(b"}}");
+    Ok(())
+}
+
+/// Write the string as a quoted JSON string, escaping it where necessary.
+pub(crate) fn write_json_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    w.write_all(b"\"")?;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        let escaped = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            c if (c as u32) < 0x20 => "",
+            _ => continue,
+        };
+        w.write_all(&s.as_bytes()[start..i])?;
+        if escaped.is_empty() {
+            write!(w, "\\u{:04x}", c as u32)?;
+        } else {
+            w.write_all(escaped.as_bytes())?;
+        }
+        start = i + c.len_utf8();
+    }
+    w.write_all(&s.as_bytes()[start..])?;
+    w.write_all(b"\"")
+}
@@ -0,0 +1,205 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::watch::{watch, POLL_INTERVAL};
+use crate::{percent_decode, Error};
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+static VAR_PORT: &str = "BLADES_PORT";
+const DEFAULT_PORT: u16 = 8000;
+/// The path the injected script listens on for reload events.
+static RELOAD_PATH: &str = "/__blades/reload";
+
+/// Serve the output directory on localhost, while watching the site and rebuilding it
+/// whenever any of its sources change, reloading the open pages in the browser.
+pub(crate) fn serve(config_name: &str, output_dir: &str, drafts: bool) -> Result<(), Error> {
+    let port = env::var(VAR_PORT)
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let generation = Arc::new(AtomicUsize::new(0));
+
+    let root = PathBuf::from(output_dir);
+    let gen = Arc::clone(&generation);
+    thread::spawn(move || listen(listener, root, gen));
+    println!("Serving the site on {}.", url);
+
+    // Links should lead to the local server instead of the deployed site.
+    watch(config_name, Some(&url), drafts, || {
+        generation.fetch_add(1, Ordering::AcqRel);
+    })
+}
+
+/// Accept the connections, handling each of them in a separate thread.
+fn listen(listener: TcpListener, root: PathBuf, generation: Arc<AtomicUsize>) {
+    let root = Arc::new(root);
+    for stream in listener.incoming().filter_map(Result::ok) {
+        let root = Arc::clone(&root);
+        let generation = Arc::clone(&generation);
+        // The browser closing the connection is not an error worth reporting.
+        thread::spawn(move || respond(stream, &root, &generation));
+    }
+}
+
+/// Respond to one HTTP request.
+fn respond(mut stream: TcpStream, root: &Path, generation: &AtomicUsize) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let head = method == "HEAD";
+    if method != "GET" && !head {
+        let body = b"Method not allowed";
+        return write_response(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            body,
+            head,
+        );
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path == RELOAD_PATH {
+        return wait_for_reload(stream, query, generation);
+    }
+
+    let file = match find_file(root, &percent_decode(path)) {
+        Some(file) => file,
+        None => {
+            let body = fs::read(root.join("404.html")).unwrap_or_else(|_| b"Not found".to_vec());
+            let body = inject_script(body, generation.load(Ordering::Acquire));
+            return write_response(&mut stream, "404 Not Found", "text/html", &body, head);
+        }
+    };
+    let mime = mime_type(&file);
+    let mut body = fs::read(&file)?;
+    if mime == "text/html" {
+        body = inject_script(body, generation.load(Ordering::Acquire));
+    }
+    write_response(&mut stream, "200 OK", mime, &body, head)
+}
+
+fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    mime: &str,
+    body: &[u8],
+    head: bool,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        mime,
+        body.len()
+    )?;
+    if !head {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+/// Keep the event stream open until the site is rebuilt (its generation differs from the one
+/// the page was served in), then tell the page to reload itself.
+fn wait_for_reload(mut stream: TcpStream, query: &str, generation: &AtomicUsize) -> io::Result<()> {
+    let seen = query.parse().unwrap_or(0);
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+          Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+    )?;
+    loop {
+        if generation.load(Ordering::Acquire) != seen {
+            stream.write_all(b"data: reload\n\n")?;
+            return stream.flush();
+        }
+        // A comment line, so that closed connections are detected and their threads end.
+        stream.write_all(b": waiting\n\n")?;
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Insert the live reload script before the end of the body of the HTML page.
+fn inject_script(mut html: Vec<u8>, generation: usize) -> Vec<u8> {
+    let script = format!(
+        "<script>new EventSource(\"{}?{}\").onmessage = function () {{ location.reload(); }};</script>",
+        RELOAD_PATH, generation
+    );
+    let end = html
+        .windows(7)
+        .rposition(|w| w.eq_ignore_ascii_case(b"</body>"))
+        .unwrap_or(html.len());
+    html.splice(end..end, script.bytes());
+    html
+}
+
+/// Find the file in the output directory that corresponds to the requested path.
+fn find_file(root: &Path, path: &str) -> Option<PathBuf> {
+    let mut file = root.to_path_buf();
+    for segment in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        if segment == ".." || segment.contains('\\') {
+            return None;
+        }
+        file.push(segment);
+    }
+    if file.is_dir() {
+        file.push("index.html");
+    } else if !file.exists() && file.extension().is_none() {
+        file.set_extension("html");
+    }
+    Some(file).filter(|f| f.is_file())
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" => "text/plain",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
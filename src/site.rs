@@ -13,7 +13,7 @@ use ramhorns::Content;
 use serde::{Deserialize, Serialize};
 
 /// Information about the site usable in templates
-#[derive(Content, Default, Deserialize, Serialize)]
+#[derive(Clone, Content, Default, Deserialize, Serialize)]
 pub struct Site<'c> {
     /// Where the assets will be copied to, relative to the site directory.
     #[serde(borrow, default = "default_assets")]
@@ -42,16 +42,54 @@ pub struct Site<'c> {
     /// URL of the site
     #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
     pub url: Cow<'c, str>,
+    /// URL of the pages in the language of the site: the `url`, followed by the code
+    /// of the language for the other languages than the default one.
+    #[serde(skip)]
+    pub lang_url: Cow<'c, str>,
 
     /// Generate a sitemap?
     #[serde(default = "default_true")]
     pub sitemap: bool,
+    /// Paths relative to the site, such as `tags/` or `drafts`, to leave out of the sitemap
+    /// together with everything they are the start of.
+    #[serde(default)]
+    #[ramhorns(skip)]
+    pub sitemap_exclude: Box<[Cow<'c, str>]>,
     /// Generate RSS feed?
     #[serde(default = "default_true")]
     pub rss: bool,
     /// Generate Atom feed?
     #[serde(default = "default_true")]
     pub atom: bool,
+    /// Generate JSON feed?
+    #[serde(default)]
+    pub json_feed: bool,
+    /// Settings of the feeds.
+    #[serde(default)]
+    pub feed: FeedConfig,
+    /// Other meta files to render, such as `robots.txt`, each with the template of the same name.
+    #[serde(default)]
+    #[ramhorns(skip)]
+    pub meta_outputs: Box<[Cow<'c, str>]>,
+    /// Generate a search index?
+    #[serde(default)]
+    pub search_index: bool,
+    /// Add links to themselves to the headings of the page content?
+    #[serde(default)]
+    pub heading_anchors: bool,
+    /// Number of paragraphs of the content to use as the excerpt, if there's no `<!-- more -->`.
+    #[serde(default = "default_summary_paragraphs")]
+    pub summary_paragraphs: usize,
+    /// Maximal number of words of the automatic summary, 0 if not limited.
+    #[serde(default)]
+    pub summary_words: usize,
+    /// Minify the rendered HTML and XML files and the CSS and JavaScript assets?
+    #[serde(default)]
+    pub minify: bool,
+
+    /// URLs of the fingerprinted assets, by their names.
+    #[serde(skip)]
+    pub asset: HashMap<String, String>,
 
     /// Extra values provided by the user
     #[serde(flatten)]
@@ -59,11 +97,56 @@ pub struct Site<'c> {
     pub extra: HashMap<&'c str, Any<'c>>,
 }
 
+impl Site<'_> {
+    /// URL the permalinks of the pages start with: the `lang_url`, or the `url` if it's not set.
+    #[inline]
+    pub fn pages_url(&self) -> &str {
+        if self.lang_url.is_empty() {
+            &self.url
+        } else {
+            &self.lang_url
+        }
+    }
+}
+
+/// Settings of the feeds of the site and of its sections and taxonomies.
+#[derive(Clone, Content, Deserialize, Serialize)]
+pub struct FeedConfig {
+    /// Maximal number of entries of a feed, 0 if not limited.
+    #[serde(default)]
+    pub limit: usize,
+    /// Order the entries by their date, the newest first, instead of the order of the pages.
+    #[serde(default)]
+    pub sort_by_date: bool,
+    /// Include the sections in the feeds?
+    #[serde(default = "default_true")]
+    pub sections: bool,
+    /// Use the summaries of the pages as the content of the entries, instead of the full content.
+    #[serde(default)]
+    pub summary_only: bool,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            limit: 0,
+            sort_by_date: false,
+            sections: true,
+            summary_only: false,
+        }
+    }
+}
+
 #[inline]
 const fn default_assets() -> Cow<'static, str> {
     Cow::const_str("assets")
 }
 
+#[inline]
+const fn default_summary_paragraphs() -> usize {
+    1
+}
+
 #[inline]
 pub(crate) const fn default_true() -> bool {
     true
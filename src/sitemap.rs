@@ -0,0 +1,108 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::page::{Page, Permalink, Translation};
+use crate::site::Site;
+use crate::taxonomies::Classification;
+use crate::types::DateTime;
+
+use ramhorns::Content;
+
+/// The maximal number of URLs in one sitemap, as given by the protocol.
+pub(crate) const MAX_URLS: usize = 50_000;
+
+/// One entry of the sitemap or of the sitemap index.
+#[derive(Content)]
+pub(crate) struct Url<'r> {
+    /// Full URL of the page.
+    loc: String,
+    /// Date of the last update of the page, or of any of the pages in the sitemap.
+    lastmod: Option<DateTime>,
+    changefreq: &'r str,
+    priority: f32,
+    translations: &'r [Translation],
+}
+
+impl<'r> Url<'r> {
+    /// The entry of the sitemap index for the sitemap at `loc` with the given URLs.
+    pub(crate) fn index(loc: String, urls: &[Url<'_>]) -> Self {
+        Url {
+            loc,
+            lastmod: urls.iter().filter_map(|url| url.lastmod).max(),
+            changefreq: "",
+            priority: 0.0,
+            translations: &[],
+        }
+    }
+}
+
+/// The URLs of all the pages and taxonomies for the sitemap, except for those
+/// with `exclude_from_sitemap` and those starting with one of the paths in `sitemap_exclude`.
+/// The last modification of a page is its `updated` date, or if not given, its `date`;
+/// the one of a taxonomy or its key is the newest one of its pages.
+pub(crate) fn urls<'r>(
+    pages: &[&'r Page<'_>],
+    taxons: &Classification<'_, '_>,
+    site: &Site<'_>,
+) -> Vec<Url<'r>> {
+    let url = site.pages_url().trim_end_matches('/');
+    let excluded = |loc: &str| {
+        let path = loc[url.len()..].trim_start_matches('/');
+        site.sitemap_exclude
+            .iter()
+            .any(|rule| path.starts_with(rule.trim_start_matches('/')))
+    };
+
+    let mut urls = Vec::with_capacity(pages.len());
+    for page in pages.iter().filter(|page| !page.exclude_from_sitemap) {
+        let loc = Permalink(page, url).to_string();
+        if !excluded(&loc) {
+            urls.push(Url {
+                loc,
+                lastmod: lastmod(page),
+                changefreq: &page.changefreq,
+                priority: page.priority,
+                translations: &page.translations,
+            });
+        }
+    }
+
+    let mut taxons: Vec<_> = taxons.iter().collect();
+    taxons.sort_unstable_by_key(|(slug, _)| **slug);
+    for (slug, taxonomy) in taxons {
+        let taxonomy_loc = format!("{}/{}/", url, slug);
+        if !excluded(&taxonomy_loc) {
+            let pages = taxonomy.keys().values().flatten();
+            urls.push(Url {
+                lastmod: pages.filter_map(|page| lastmod(page.0)).max(),
+                loc: taxonomy_loc.clone(),
+                changefreq: "",
+                priority: 0.2,
+                translations: &[],
+            });
+        }
+        for (key, pages) in taxonomy.keys() {
+            let loc = format!("{}{}/", taxonomy_loc, key);
+            if !excluded(&loc) {
+                urls.push(Url {
+                    loc,
+                    lastmod: pages.iter().filter_map(|page| lastmod(page.0)).max(),
+                    changefreq: "",
+                    priority: 0.3,
+                    translations: &[],
+                });
+            }
+        }
+    }
+    urls
+}
+
+#[inline]
+fn lastmod(page: &Page<'_>) -> Option<DateTime> {
+    page.updated.or(page.date)
+}
@@ -53,6 +53,8 @@ pub struct Sources<P: Parser> {
     pub(crate) data: Vec<u8>,
     /// Info about where the particular files are loaded
     sources: Vec<Source<P>>,
+    /// Paths of the other files in the content directory, which are not pages
+    assets: Vec<PathBuf>,
 }
 
 impl<P: Parser> Source<P> {
@@ -106,6 +108,7 @@ impl<P: Parser> Sources<P> {
     ) -> Result<(), io::Error> {
         let start = self.sources.len();
         let mut index_file = None;
+        let assets = &mut self.assets;
         for (path, date, format) in read_dir(&path)?
             .filter_map(Result::ok)
             .filter(|entry| {
@@ -127,7 +130,13 @@ impl<P: Parser> Sources<P> {
             })
             .filter_map(|(path, date)| {
                 let ext = path.extension().unwrap_or_default();
-                let format = P::from_extension(ext)?;
+                let Some(format) = P::from_extension(ext) else {
+                    // Hidden files, like .gitignore, are not a part of the site
+                    if !path.file_name()?.to_string_lossy().starts_with('.') {
+                        assets.push(path);
+                    }
+                    return None;
+                };
                 if path.file_stem()? == "index" {
                     index_file = Some((path, date, format));
                     return None;
@@ -185,6 +194,7 @@ impl<P: Parser> Sources<P> {
         let mut sources = Self {
             data: Vec::with_capacity(65536),
             sources: Vec::with_capacity(64),
+            assets: Vec::new(),
         };
         sources.sources.push(Source::empty(dir.into(), 0));
 
@@ -205,4 +215,10 @@ impl<P: Parser> Sources<P> {
     pub fn sources(&self) -> &[Source<P>] {
         &self.sources
     }
+
+    /// Paths of the files in the content directory that are not pages,
+    /// such as images belonging to them.
+    pub fn assets(&self) -> &[PathBuf] {
+        &self.assets
+    }
 }
@@ -7,9 +7,9 @@
 // You should have received a copy of the GNU General Public License
 // along with Blades.  If not, see <http://www.gnu.org/licenses/>
 use crate::page::{Context, Page, PageRef, Paginate, Pagination, Permalink};
-use crate::render::render;
+use crate::render::{render, render_feeds, Rendered};
 use crate::site::Site;
-use crate::types::HashMap;
+use crate::types::{Any, HashMap};
 
 use arrayvec::ArrayVec;
 use beef::lean::Cow;
@@ -22,7 +22,6 @@ use std::collections::BTreeMap;
 use std::fs::create_dir_all;
 use std::num::NonZeroUsize;
 use std::ops::{Deref, Range};
-use std::path::PathBuf;
 
 const DEFAULT_TEMPLATE: &str = "taxonomy.html";
 const DEFAULT_KEY_TEMPLATE: &str = "taxonomy_key.html";
@@ -66,6 +65,10 @@ pub struct TaxonMeta<'t> {
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     #[ramhorns(skip)]
     pub sort_by_weight: bool,
+    /// Render the feeds of the pages for every key into its directory
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[ramhorns(skip)]
+    pub feeds: bool,
 }
 
 /// One taxonomical category of the site (e.g. tags, categories).
@@ -89,6 +92,7 @@ struct TaxKey<'t, 'r> {
     index: PageRef<'t, 'r>,
     site: &'r Site<'t>,
     classification: &'r Classification<'t, 'r>,
+    data: &'r Any<'t>,
     pagination: Option<Pagination>,
 }
 
@@ -100,6 +104,7 @@ struct TaxContext<'t, 'r> {
     index: PageRef<'t, 'r>,
     site: &'r Site<'t>,
     classification: &'r Classification<'t, 'r>,
+    data: &'r Any<'t>,
 }
 
 /// Classification of the whole site that's rendered as a list instead of a map (for sitemap)
@@ -115,7 +120,7 @@ struct Coupled<'t, 'r>(
 /// Reference to a page, coupled with it's permalink
 #[derive(Content)]
 pub struct PageLinked<'t, 'r>(
-    #[ramhorns(flatten)] &'r Page<'t>,
+    #[ramhorns(flatten)] pub(crate) &'r Page<'t>,
     #[ramhorns(rename = "permalink")] Permalink<'t, 'r>,
 );
 
@@ -130,6 +135,7 @@ impl<'t, 'r> Taxonomy<'t, 'r> {
                 key_template: Cow::const_str("taxonomy_key.html"),
                 paginate_by: None,
                 sort_by_weight: false,
+                feeds: false,
             },
             slug,
             keys: TaxDict(Default::default()),
@@ -146,6 +152,7 @@ impl<'t, 'r> Taxonomy<'t, 'r> {
                 key_template: Cow::const_str(&other.key_template),
                 paginate_by: other.paginate_by,
                 sort_by_weight: other.sort_by_weight,
+                feeds: other.feeds,
             },
             slug,
             keys: TaxDict(Default::default()),
@@ -175,7 +182,7 @@ impl<'t, 'r> Taxonomy<'t, 'r> {
                 .collect(),
         );
 
-        for page in pages {
+        for page in pages.iter().filter(|p| !p.excluded) {
             for (class, family) in page.taxonomies.iter() {
                 if let Some(taxon) = named.get_mut(class) {
                     for species in family {
@@ -225,8 +232,8 @@ impl<'t, 'r> Taxonomy<'t, 'r> {
     #[inline]
     pub fn render(
         &self,
-        Context(all, site, classification, templates, output_dir): Context<'t, '_>,
-        rendered: &mut Vec<PathBuf>,
+        Context(all, site, classification, templates, output_dir, data): Context<'t, '_>,
+        rendered: &mut Rendered<'_>,
         buffer: &mut Vec<u8>,
     ) -> Result<(), Error> {
         let mut path = output_dir.join(self.slug);
@@ -236,8 +243,9 @@ impl<'t, 'r> Taxonomy<'t, 'r> {
         let contexted = TaxContext {
             taxonomy: self,
             site,
-            index: all[0].by_ref(all, usize::MAX, &site.url),
+            index: all[0].by_ref(all, usize::MAX, site.pages_url()),
             classification,
+            data,
         };
         let template = templates
             .get(&self.taxonomy.template)
@@ -252,22 +260,29 @@ impl<'t, 'r> Taxonomy<'t, 'r> {
         &self,
         title: &str,
         pages: &[PageLinked<'t, '_>],
-        Context(all, site, classification, templates, output_dir): Context<'t, '_>,
-        rendered: &mut Vec<PathBuf>,
+        Context(all, site, classification, templates, output_dir, data): Context<'t, '_>,
+        rendered: &mut Rendered<'_>,
         buffer: &mut Vec<u8>,
     ) -> Result<(), Error> {
         let mut output = output_dir.join(self.slug);
         output.push(title);
         create_dir_all(&output)?;
+        if self.taxonomy.feeds {
+            let feed_pages = pages.iter().map(|page| page.0).filter(|page| !page.hidden);
+            let context = Context(all, site, classification, templates, output_dir, data);
+            let path = format!("{}/{}", self.slug, title);
+            render_feeds(context, feed_pages.collect(), &path, rendered, buffer)?;
+        }
         output.push("index.html");
 
         let contexted = TaxKey {
             title,
             taxonomy: self,
             pages,
-            index: all[0].by_ref(all, usize::MAX, &site.url),
+            index: all[0].by_ref(all, usize::MAX, site.pages_url()),
             site,
             classification,
+            data,
             pagination: None,
         };
 
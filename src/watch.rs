@@ -0,0 +1,174 @@
+// Blades  Copyright (C) 2021 Maroš Grego
+//
+// This file is part of Blades. This program comes with ABSOLUTELY NO WARRANTY;
+// This is free software, and you are welcome to redistribute it under the
+// conditions of the GNU General Public License version 3.0.
+//
+// You should have received a copy of the GNU General Public License
+// along with Blades.  If not, see <http://www.gnu.org/licenses/>
+use crate::data::DATA_DIR;
+use crate::{assets, build, Config, Error, ASSET_SRC_DIR, TEMPLATE_DIR};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the watched files are checked for changes.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// The newest modification time and the number of files in the watched paths.
+#[derive(PartialEq, Eq)]
+struct Stamp {
+    newest: Option<SystemTime>,
+    count: usize,
+}
+
+/// Paths that are watched for changes, grouped by what needs to be done when they change.
+struct Watched {
+    /// Config, templates and data; when they change, everything is rendered again.
+    global: Vec<PathBuf>,
+    /// Content; when it changes, only the affected pages are rendered.
+    content: Vec<PathBuf>,
+    /// Assets; when they change, they are colocated again.
+    assets: Vec<PathBuf>,
+    /// Are the assets fingerprinted? Then everything is rendered again when they change.
+    fingerprinted: bool,
+}
+
+impl Stamp {
+    fn of(paths: &[PathBuf]) -> Self {
+        let mut stamp = Stamp {
+            newest: None,
+            count: 0,
+        };
+        for path in paths {
+            stamp.visit(path);
+        }
+        stamp
+    }
+
+    fn visit(&mut self, path: &Path) {
+        let meta = match fs::metadata(path) {
+            Ok(meta) => meta,
+            Err(_) => return,
+        };
+        self.count += 1;
+        if let Ok(modified) = meta.modified() {
+            self.newest = self.newest.max(Some(modified));
+        }
+        if meta.is_dir() {
+            if let Ok(iter) = fs::read_dir(path) {
+                for entry in iter.filter_map(Result::ok) {
+                    self.visit(&entry.path());
+                }
+            }
+        }
+    }
+}
+
+impl Watched {
+    fn stamps(&self) -> [Stamp; 3] {
+        [
+            Stamp::of(&self.global),
+            Stamp::of(&self.content),
+            Stamp::of(&self.assets),
+        ]
+    }
+}
+
+/// Build the site and watch its sources, rendering again only what was affected by a change.
+/// If `url` is provided, it is used instead of the site URL from the config.
+/// If `drafts` is set, drafts and unpublished pages are rendered, too.
+/// `rebuilt` is called after every successful build.
+pub(crate) fn watch<F: FnMut()>(
+    config_name: &str,
+    url: Option<&str>,
+    drafts: bool,
+    mut rebuilt: F,
+) -> Result<(), Error> {
+    let mut dependencies = Vec::new();
+    let mut watched = rebuild(config_name, url, drafts, &mut dependencies, true)?;
+    rebuilt();
+    println!("Watching for changes, press Ctrl+C to stop.");
+
+    let mut stamps = watched.stamps();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let current = watched.stamps();
+        if current == stamps {
+            continue;
+        }
+        let global = current[0] != stamps[0];
+        let assets = current[2] != stamps[2];
+        if global || (assets && watched.fingerprinted) {
+            dependencies.clear();
+        }
+
+        let start = Instant::now();
+        match rebuild(
+            config_name,
+            url,
+            drafts,
+            &mut dependencies,
+            global || assets,
+        ) {
+            Ok(w) => {
+                watched = w;
+                rebuilt();
+                println!(
+                    "Rebuilt in {}ms.",
+                    start.elapsed().as_micros() as f64 / 1000.0
+                );
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+        stamps = watched.stamps();
+    }
+}
+
+/// Read the config, build the site and colocate the assets if required.
+/// Return the paths that should be watched for changes.
+fn rebuild(
+    config_name: &str,
+    url: Option<&str>,
+    drafts: bool,
+    dependencies: &mut Vec<blades::Dependencies>,
+    colocate: bool,
+) -> Result<Watched, Error> {
+    let config_file = fs::read_to_string(config_name)?;
+    let mut config: Config =
+        toml::from_str(&config_file).map_err(|e| Error::Parse(e.into(), config_name.into()))?;
+    if let Some(url) = url {
+        config.site.url = url.into();
+    }
+
+    if colocate {
+        let assets = assets::assets(&config)?;
+        build(&config, dependencies, drafts, Some(&assets))?;
+        assets::colocate(&config, &assets)?;
+    } else {
+        build(&config, dependencies, drafts, None)?;
+    }
+
+    let mut watched = Watched {
+        global: vec![
+            PathBuf::from(config_name),
+            PathBuf::from(TEMPLATE_DIR),
+            PathBuf::from(DATA_DIR),
+        ],
+        content: vec![PathBuf::from(config.content_dir.as_ref())],
+        assets: vec![PathBuf::from(ASSET_SRC_DIR)],
+        fingerprinted: config.fingerprint_assets,
+    };
+    for content_dir in config.languages.values() {
+        watched.content.push(PathBuf::from(content_dir.as_ref()));
+    }
+    if !config.theme.is_empty() {
+        let theme = Path::new(config.theme_dir.as_ref()).join(config.theme.as_ref());
+        watched.global.push(theme.join(TEMPLATE_DIR));
+        watched.global.push(theme.join(DATA_DIR));
+        watched.assets.push(theme.join(ASSET_SRC_DIR));
+    }
+    Ok(watched)
+}